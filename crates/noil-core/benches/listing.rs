@@ -0,0 +1,71 @@
+//! Benchmarks listing a directory as a buffer over synthetic trees of increasing size.
+//!
+//! `parallel` is [`noil_core::render`], `sequential` is the baseline it replaced: a single
+//! threaded walk, with the walked paths tagged the same way.
+//!
+//! Run with `cargo bench -p noil-core --bench listing`.
+
+use std::path::{Path, PathBuf};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+/// Creates `dirs` directories with `files_per_dir` empty files each, nested two levels deep to
+/// look a bit more like a real project
+fn synthetic_tree(root: &Path, dirs: usize, files_per_dir: usize) -> std::io::Result<()> {
+    for dir in 0..dirs {
+        let dir = root
+            .join(format!("pkg-{}", dir % 16))
+            .join(format!("mod-{dir}"));
+        std::fs::create_dir_all(&dir)?;
+
+        for file in 0..files_per_dir {
+            std::fs::File::create(dir.join(format!("file-{file}.rs")))?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn sequential(root: &Path) -> anyhow::Result<String> {
+    let paths = ignore::WalkBuilder::new(root)
+        .build()
+        .map(|entry| Ok(entry?.into_path()))
+        .collect::<anyhow::Result<Vec<PathBuf>>>()?;
+
+    noil_core::render_paths(&paths, true).await
+}
+
+fn listing(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("create runtime");
+
+    let mut group = c.benchmark_group("listing");
+    group.sample_size(10);
+
+    for (dirs, files_per_dir) in [(10, 100), (100, 100), (500, 200)] {
+        let root = tempfile::tempdir().expect("create temp dir");
+        synthetic_tree(root.path(), dirs, files_per_dir).expect("create synthetic tree");
+        let files = dirs * files_per_dir;
+
+        group.bench_with_input(
+            BenchmarkId::new("parallel", files),
+            root.path(),
+            |b, root| {
+                b.iter(|| {
+                    runtime
+                        .block_on(noil_core::render(root, true))
+                        .expect("render")
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sequential", files),
+            root.path(),
+            |b, root| b.iter(|| runtime.block_on(sequential(root)).expect("render")),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, listing);
+criterion_main!(benches);
@@ -0,0 +1,918 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    fs::{FileKind, FileSystem, RealFileSystem, WalkEntry, WalkOptions},
+    guard::{self, Guard},
+    hooks::Hooks,
+    models::{Buffer, Operation},
+    parse,
+    plan::{self, Plan, Step},
+    progress::{Progress, ProgressTotal},
+    report::{Report, ReportEntry, Status},
+    syntax::Syntax,
+};
+
+#[derive(Default, Clone, Debug)]
+pub struct ApplyOptions {
+    /// Write the paths of `OPEN` entries to this file, separated by spaces
+    pub chooser_file: Option<PathBuf>,
+    pub quiet: bool,
+    /// Draw a progress bar on stderr while applying
+    pub progress: bool,
+    /// How many independent operations may run at the same time
+    pub jobs: usize,
+    /// What to do when the destination of an ADD, COPY or MOVE already exists
+    pub conflict: Conflict,
+    /// Move deleted and replaced paths below this directory instead of removing them
+    pub trash: Option<PathBuf>,
+    /// Aliases and user defined operations the buffer may use
+    pub syntax: Syntax,
+    /// Commands to run before and after the plan and every operation, see [`crate::hooks`]
+    pub hooks: Hooks,
+    /// Refuses plans which touch protected paths or delete too many files, see [`crate::guard`]
+    pub guard: Guard,
+    /// Refuses plans with sources or destinations outside this directory, see
+    /// [`guard::confine`]
+    pub confine: Option<PathBuf>,
+}
+
+/// What to do when the destination of an operation already exists
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    /// Never replace anything, ADD and COPY skip an existing destination, MOVE fails
+    #[default]
+    Keep,
+    /// Skip the operation
+    Skip,
+    /// Fail the operation
+    Fail,
+    /// Delete the destination, or move it to the trash, and perform the operation
+    Overwrite,
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "skip" => Ok(Self::Skip),
+            "fail" => Ok(Self::Fail),
+            "overwrite" => Ok(Self::Overwrite),
+            s => anyhow::bail!(
+                "unknown conflict policy: {s}, expected keep, skip, fail or overwrite"
+            ),
+        }
+    }
+}
+
+/// the philosphy behind apply is that we try unlike normal file system operations to be idempotent.
+/// This is mainly for 2 reasons.
+///
+/// 1. A lot of operations are processed, stopping in the middle because of an error would ruing your previous procedure that you now have to go back and fix
+/// 2. A .noil recipe can be rerun, having small issues disrupt the work would be counterproductive, as the .noil language is not powerful enough to handle the flexibility required for file checking
+///
+/// All in all apply is mostly idempotent, and won't override files, it tries to be as non destructive as possible. For example move will only throw a warning if the source file doesn't exists, but the destination does
+pub async fn apply(input: &str, options: ApplyOptions) -> anyhow::Result<()> {
+    apply_with(&RealFileSystem, input, options).await
+}
+
+/// Same as [`apply`], but performs the operations on the given filesystem. The chooser file is
+/// always written to disk.
+pub async fn apply_with(
+    fs: &dyn FileSystem,
+    input: &str,
+    options: ApplyOptions,
+) -> anyhow::Result<()> {
+    if !options.quiet {
+        eprintln!("applying changes");
+    }
+
+    let (noil_index, plan, progress) = prepare(fs, input, &options).await?;
+
+    let results = run_plan(fs, &plan, &options, &progress).await;
+    progress.finish();
+
+    options.hooks.post_apply(&report(&plan, &results)).await?;
+    collect_errors(results)?;
+
+    if let Some(chooser_file) = &options.chooser_file {
+        write_chooser_file(&noil_index, chooser_file).await?;
+    }
+
+    Ok(())
+}
+
+/// Same as [`apply_with`], but describes the result of every operation, see [`crate::report`].
+///
+/// Failed operations are part of the report rather than returned as an error, use
+/// [`Report::is_success`] to check whether everything was applied. The chooser file is only
+/// written if everything was applied.
+pub async fn apply_report(
+    fs: &dyn FileSystem,
+    input: &str,
+    options: ApplyOptions,
+) -> anyhow::Result<Report> {
+    let (noil_index, plan, progress) = prepare(fs, input, &options).await?;
+
+    let results = run_plan(fs, &plan, &options, &progress).await;
+    progress.finish();
+
+    let report = report(&plan, &results);
+    options.hooks.post_apply(&report).await?;
+
+    if report.is_success()
+        && let Some(chooser_file) = &options.chooser_file
+    {
+        write_chooser_file(&noil_index, chooser_file).await?;
+    }
+
+    Ok(report)
+}
+
+/// Parses and plans the buffer, and runs every check and the `pre_apply` hooks. The progress bar
+/// is only drawn once all of them have passed.
+async fn prepare(
+    fs: &dyn FileSystem,
+    input: &str,
+    options: &ApplyOptions,
+) -> anyhow::Result<(Buffer, Plan, Progress)> {
+    let noil_index = options
+        .syntax
+        .resolve(parse::parse_input(input).context("parse input")?)?;
+    let plan = plan::plan(&noil_index);
+
+    if let Some(root) = &options.confine {
+        guard::confine(fs, root, &plan).await?;
+    }
+    options.guard.check(fs, &plan).await?;
+    options.hooks.pre_apply(&plan).await?;
+
+    let progress = if options.progress {
+        Progress::new(progress_total(fs, &plan).await, true)
+    } else {
+        Progress::hidden()
+    };
+
+    Ok((noil_index, plan, progress))
+}
+
+fn report(plan: &Plan, results: &[Option<anyhow::Result<Status>>]) -> Report {
+    Report {
+        entries: plan
+            .steps
+            .iter()
+            .zip(results)
+            .map(|(step, res)| match res {
+                Some(Ok(status)) => ReportEntry::step(step, *status, None),
+                Some(Err(e)) => ReportEntry::step(step, Status::Failed, Some(format!("{e:#}"))),
+                None => ReportEntry::step(step, Status::NotRun, None),
+            })
+            .collect(),
+    }
+}
+
+async fn write_chooser_file(noil_index: &Buffer, chooser_file: &Path) -> anyhow::Result<()> {
+    tracing::debug!("creating chooser file");
+    if let Some(parent) = chooser_file.parent()
+        && !chooser_file.exists()
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("parent dir for chooser file")?;
+    }
+
+    let mut file = tokio::fs::File::create(chooser_file)
+        .await
+        .context("create new chooser file")?;
+
+    let open_files = noil_index
+        .files
+        .iter()
+        .filter(|f| matches!(f.entry.operation, Operation::Open { .. }))
+        // We can't open directories, so they're skipped
+        .filter(|f| !f.path.to_string_lossy().ends_with("/"))
+        .map(|f| f.path.display().to_string())
+        .collect::<Vec<_>>();
+
+    file.write_all(open_files.join(" ").as_bytes())
+        .await
+        .context("write chosen files")?;
+    file.flush().await.context("flush chosen file")?;
+
+    Ok(())
+}
+
+/// Walks the plan up front, so that the progress bar knows how much work is ahead of it
+async fn progress_total(fs: &dyn FileSystem, plan: &Plan) -> ProgressTotal {
+    let mut total = ProgressTotal::default();
+
+    for step in &plan.steps {
+        match &step.operation {
+            Operation::Existing { .. } | Operation::Open { .. } => {}
+            Operation::Add
+            | Operation::Move { .. }
+            | Operation::Link { .. }
+            | Operation::Command { .. } => total.files += 1,
+            Operation::Copy { .. } => {
+                if let Some(source) = &step.source {
+                    total.add(ProgressTotal::of_path(fs, source).await);
+                }
+            }
+            Operation::Delete { .. } => total.add(ProgressTotal::of_path(fs, &step.path).await),
+        }
+    }
+
+    total
+}
+
+/// Runs the batches of the plan one after another. Up to `jobs` steps run concurrently within a
+/// batch, with a single job the steps run one by one in buffer order.
+///
+/// If any step in a batch fails, the remaining batches are not started. All errors of the failed
+/// batch are reported in buffer order.
+pub async fn apply_plan(
+    fs: &dyn FileSystem,
+    plan: &Plan,
+    options: &ApplyOptions,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    collect_errors(run_plan(fs, plan, options, progress).await)
+}
+
+fn collect_errors(results: Vec<Option<anyhow::Result<Status>>>) -> anyhow::Result<()> {
+    let mut errors = results
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r.err())
+        .collect::<Vec<_>>();
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        n => {
+            let errors = errors
+                .iter()
+                .map(|e| format!("  - {e:#}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            anyhow::bail!("{n} operations failed:\n{errors}");
+        }
+    }
+}
+
+/// Runs the plan like [`apply_plan`], returning the result of each step, `None` for the steps
+/// which weren't started
+async fn run_plan(
+    fs: &dyn FileSystem,
+    plan: &Plan,
+    options: &ApplyOptions,
+    progress: &Progress,
+) -> Vec<Option<anyhow::Result<Status>>> {
+    let jobs = options.jobs.max(1);
+    let batches = if jobs == 1 {
+        plan.sequential()
+    } else {
+        plan.batches.clone()
+    };
+
+    let mut results = plan.steps.iter().map(|_| None).collect::<Vec<_>>();
+
+    for batch in batches {
+        let batch_results = futures::stream::iter(batch)
+            .map(|index| {
+                let step = &plan.steps[index];
+                async move {
+                    let res = apply_hooked_step(fs, step, options, progress)
+                        .await
+                        .with_context(|| format!("{} {}", step.operation, step.path.display()));
+                    (index, res)
+                }
+            })
+            .buffered(jobs)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut failed = false;
+        for (index, res) in batch_results {
+            failed |= res.is_err();
+            results[index] = Some(res);
+        }
+
+        if failed {
+            break;
+        }
+    }
+
+    results
+}
+
+/// Applies a step between its `pre_operation` and `post_operation` hooks
+async fn apply_hooked_step(
+    fs: &dyn FileSystem,
+    step: &Step,
+    options: &ApplyOptions,
+    progress: &Progress,
+) -> anyhow::Result<Status> {
+    options.hooks.pre_operation(step).await?;
+
+    let res = apply_step(fs, step, options, progress).await;
+    let hooked = options.hooks.post_operation(step, &res).await;
+    let status = res?;
+    hooked?;
+
+    Ok(status)
+}
+
+/// Performs a single step, returns [`Status::Skipped`] if there was nothing to do
+async fn apply_step(
+    fs: &dyn FileSystem,
+    step: &Step,
+    options: &ApplyOptions,
+    progress: &Progress,
+) -> anyhow::Result<Status> {
+    let path = &step.path;
+    match &step.operation {
+        Operation::Existing { .. } | Operation::Open { .. } => {
+            // Noop, these aren't part of a plan
+        }
+        Operation::Add => {
+            tracing::debug!("creating file");
+            progress.set_operation("ADD", path);
+
+            // is dir
+            let is_dir = path.to_string_lossy().ends_with("/");
+            if fs.exists(path).await && (is_dir || !replace(fs, path, options, "add").await?) {
+                tracing::warn!("path already exists");
+                progress.inc_files(1);
+                return Ok(Status::Skipped);
+            }
+
+            if is_dir {
+                fs.create_dir_all(path).await.context("add directory")?;
+                tracing::info!("added directory");
+                progress.inc_files(1);
+                return Ok(Status::Applied);
+            }
+
+            if let Some(parent) = path.parent() {
+                fs.create_dir_all(parent)
+                    .await
+                    .context("create parent dir for add file")?;
+            }
+
+            fs.create_file(path).await.context("add file")?;
+            progress.inc_files(1);
+
+            tracing::info!("added file");
+        }
+        Operation::Copy { index } => {
+            tracing::debug!("copying file");
+            progress.set_operation("COPY", path);
+
+            let existing = step.source.as_ref().ok_or(anyhow::anyhow!(
+                "entry with index: '{}' does not exist for copy",
+                index
+            ))?;
+            if !fs.exists(existing).await {
+                bail!("existing does not exist for copy")
+            }
+
+            if fs.exists(path).await && !replace(fs, path, options, "copy").await? {
+                tracing::warn!("path already exists, cannot copy");
+                let skipped = ProgressTotal::of_path(fs, existing).await;
+                progress.inc_files(skipped.files);
+                progress.inc_bytes(skipped.bytes);
+                return Ok(Status::Skipped);
+            }
+
+            if let Some(parent) = path.parent() {
+                fs.create_dir_all(parent)
+                    .await
+                    .context("create parent dir for copy")?;
+            }
+
+            if fs.is_dir(existing).await {
+                tracing::debug!("copying dir");
+                copy(fs, existing, path, progress).await?;
+                return Ok(Status::Applied);
+            }
+
+            fs.copy_file(existing, path, progress)
+                .await
+                .context("copy file for copy")?;
+            progress.inc_files(1);
+        }
+        Operation::Delete { .. } => {
+            tracing::debug!("deleting file");
+            progress.set_operation("DELETE", path);
+
+            let Some(metadata) = fs.stat(path).await? else {
+                tracing::warn!("path doesn't exist");
+                return Ok(Status::Skipped);
+            };
+
+            if let Some(trash) = &options.trash {
+                let total = if progress.is_hidden() {
+                    ProgressTotal::default()
+                } else {
+                    ProgressTotal::of_path(fs, path).await
+                };
+                move_to_trash(fs, path, trash)
+                    .await
+                    .context("move to trash for delete")?;
+                progress.inc_files(total.files);
+                progress.inc_bytes(total.bytes);
+                return Ok(Status::Applied);
+            }
+
+            if metadata.kind == FileKind::Dir {
+                delete_dir(fs, path, progress)
+                    .await
+                    .context("remove path for delete")?;
+                return Ok(Status::Applied);
+            }
+
+            fs.remove_file(path)
+                .await
+                .context("remove file for delete")?;
+            progress.inc_files(1);
+            progress.inc_bytes(metadata.len);
+        }
+        Operation::Move { .. } => {
+            tracing::debug!("moving file");
+            progress.set_operation("MOVE", path);
+
+            let existing = step.source.as_ref();
+
+            if existing.is_none() {
+                // If the destination exists, but the existing one doesn't we assume it has already been moved
+                if fs.exists(path).await {
+                    tracing::warn!("destination file looks to already have been moved");
+                    progress.inc_files(1);
+                    return Ok(Status::Skipped);
+                }
+
+                anyhow::bail!("neither existing, or destination exists for move");
+            }
+            let existing = existing.unwrap();
+
+            if fs.exists(path).await && !replace(fs, path, options, "move").await? {
+                tracing::warn!("path already exists, cannot move");
+                progress.inc_files(1);
+                return Ok(Status::Skipped);
+            }
+
+            if let Some(parent) = path.parent()
+                && !fs.exists(parent).await
+            {
+                fs.create_dir_all(parent)
+                    .await
+                    .context("failed to create dest for move")?;
+            }
+
+            fs.rename(existing, path).await.context("move path")?;
+            progress.inc_files(1);
+        }
+        Operation::Link { index } => {
+            tracing::debug!("linking file");
+            progress.set_operation("HARDLINK", path);
+
+            let existing = step.source.as_ref().ok_or(anyhow::anyhow!(
+                "entry with index: '{}' does not exist for hardlink",
+                index
+            ))?;
+            match fs.stat(existing).await? {
+                Some(metadata) if metadata.kind == FileKind::Dir => {
+                    bail!("cannot hardlink a directory")
+                }
+                Some(_) => {}
+                None => bail!("existing does not exist for hardlink"),
+            }
+
+            match fs.stat(path).await? {
+                Some(metadata) if metadata.kind == FileKind::Dir => {
+                    bail!("destination is a directory, cannot hardlink")
+                }
+                Some(_) => {
+                    // The link is created next to the destination and moved over it, such that
+                    // the destination is never missing
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let tmp = path.with_file_name(format!(".{name}.noil-link"));
+
+                    fs.link(existing, &tmp)
+                        .await
+                        .context("create link for hardlink")?;
+                    fs.rename(&tmp, path)
+                        .await
+                        .context("replace destination for hardlink")?;
+
+                    // Renaming onto a link of the same file does nothing
+                    if fs.exists(&tmp).await {
+                        fs.remove_file(&tmp)
+                            .await
+                            .context("remove link for hardlink")?;
+                    }
+                }
+                None => {
+                    if let Some(parent) = path.parent() {
+                        fs.create_dir_all(parent)
+                            .await
+                            .context("create parent dir for hardlink")?;
+                    }
+
+                    fs.link(existing, path).await.context("link for hardlink")?;
+                }
+            }
+            progress.inc_files(1);
+        }
+        Operation::Command { name, index } => {
+            tracing::debug!("running command");
+            progress.set_operation(name, path);
+
+            if index.is_some() && step.source.is_none() {
+                bail!(
+                    "entry with index: '{}' does not exist for {name}",
+                    index.as_deref().unwrap_or_default()
+                );
+            }
+            let command = options.syntax.command(name, step.source.as_deref(), path)?;
+
+            if let Some(parent) = path.parent()
+                && !parent.as_os_str().is_empty()
+                && !fs.exists(parent).await
+            {
+                fs.create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("create parent dir for {name}"))?;
+            }
+
+            fs.run_command(&command).await?;
+            progress.inc_files(1);
+        }
+    }
+
+    Ok(Status::Applied)
+}
+
+/// Makes way for an operation whose destination already exists, as the conflict policy says.
+/// Returns whether the destination has been removed, `false` to skip the operation.
+async fn replace(
+    fs: &dyn FileSystem,
+    path: &Path,
+    options: &ApplyOptions,
+    operation: &str,
+) -> anyhow::Result<bool> {
+    match options.conflict {
+        // Only a move would lose track of its source by being skipped
+        Conflict::Keep if operation != "move" => Ok(false),
+        Conflict::Skip => Ok(false),
+        Conflict::Keep | Conflict::Fail => {
+            bail!("destination already exists cannot {operation}")
+        }
+        Conflict::Overwrite => {
+            tracing::debug!("replacing existing destination");
+            if let Some(trash) = &options.trash {
+                move_to_trash(fs, path, trash).await?;
+            } else if fs.is_dir(path).await {
+                fs.remove_dir_all(path)
+                    .await
+                    .context("remove destination")?;
+            } else {
+                fs.remove_file(path).await.context("remove destination")?;
+            }
+
+            Ok(true)
+        }
+    }
+}
+
+/// Moves `path` below `trash`, at its absolute path, such that it can be put back by hand. A
+/// path trashed more than once in the same trash gets a numbered suffix.
+async fn move_to_trash(fs: &dyn FileSystem, path: &Path, trash: &Path) -> anyhow::Result<()> {
+    let absolute = std::path::absolute(path)?;
+    let relative = absolute
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .collect::<PathBuf>();
+
+    let mut dest = trash.join(&relative);
+    let mut n = 1;
+    while fs.exists(&dest).await {
+        let name = relative.file_name().unwrap_or_default().to_string_lossy();
+        dest = trash.join(&relative).with_file_name(format!("{name}.{n}"));
+        n += 1;
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs.create_dir_all(parent)
+            .await
+            .context("create trash dir")?;
+    }
+
+    tracing::debug!(dest = %dest.display(), "moving to trash");
+    if let Err(e) = fs.rename(path, &dest).await {
+        // The trash may be on another filesystem, where paths can't be renamed into
+        tracing::debug!("rename to trash failed, copying instead: {e:#}");
+        if fs.is_dir(path).await {
+            copy(fs, path, &dest, &Progress::hidden()).await?;
+            fs.remove_dir_all(path).await?;
+        } else {
+            fs.copy_file(path, &dest, &Progress::hidden()).await?;
+            fs.remove_file(path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn copy(
+    fs: &dyn FileSystem,
+    source: &Path,
+    dest: &Path,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let entries = fs.walk(source, WalkOptions::default()).await?;
+
+    for entry in entries {
+        tracing::debug!("copying path: {}", entry.path.display());
+
+        let path = entry.path.strip_prefix(source)?;
+        let dest = dest.join(path);
+
+        copy_path(fs, &entry, &dest, progress)
+            .await
+            .context(anyhow::anyhow!(
+                "copy path: (src: {}, dest: {})",
+                entry.path.display(),
+                dest.display()
+            ))?;
+    }
+
+    Ok(())
+}
+
+async fn copy_path(
+    fs: &dyn FileSystem,
+    src: &WalkEntry,
+    dest: &Path,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs.create_dir_all(parent)
+            .await
+            .context("copy dir create parent dir")?;
+    }
+
+    let is_dir = match src.kind {
+        FileKind::Dir => true,
+        FileKind::File => false,
+        FileKind::Symlink => fs.is_dir(&src.path).await,
+    };
+
+    if is_dir {
+        tracing::info!("copying dir: {}", dest.display());
+        fs.create_dir_all(dest).await.context("copy dir")?;
+    } else {
+        tracing::info!("copying file: {}", dest.display());
+        fs.copy_file(&src.path, dest, progress)
+            .await
+            .context("copy file")?;
+    }
+    progress.inc_files(1);
+
+    Ok(())
+}
+
+/// Deletes a directory one entry at a time, contents before their parents, to be able to report
+/// progress on large trees
+async fn delete_dir(fs: &dyn FileSystem, path: &Path, progress: &Progress) -> anyhow::Result<()> {
+    if progress.is_hidden() {
+        fs.remove_dir_all(path).await?;
+        return Ok(());
+    }
+
+    let entries = fs.walk(path, WalkOptions::default()).await?;
+
+    // Walks are sorted parents first, reversing it gives us the contents first
+    for entry in entries.into_iter().rev() {
+        if entry.kind == FileKind::Dir {
+            fs.remove_dir(&entry.path).await?;
+        } else {
+            let len = match fs.stat(&entry.path).await {
+                Ok(Some(metadata)) if entry.kind == FileKind::File => metadata.len,
+                _ => 0,
+            };
+            fs.remove_file(&entry.path).await?;
+            progress.inc_bytes(len);
+        }
+
+        progress.inc_files(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{
+        apply::{ApplyOptions, Conflict, apply_report, apply_with},
+        fs::MemoryFileSystem,
+        report::Status,
+    };
+
+    fn options() -> ApplyOptions {
+        ApplyOptions {
+            quiet: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn can_apply_operations() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/nested/b.txt", "b")
+            .with_file("/old.txt", "old")
+            .with_file("/remove.txt", "remove");
+
+        let input = r#"
+           abc   :   /src/
+           ecd   :   /old.txt
+           fgh   :   /remove.txt
+COPY       abc   :   /copy/
+MOVE       ecd   :   /moved/new.txt
+DELETE     fgh   :   /remove.txt
+ADD              :   /added/file.txt
+ADD              :   /added/dir/
+"#;
+
+        apply_with(&fs, input, options()).await?;
+
+        pretty_assertions::assert_eq!(
+            vec![
+                "/",
+                "/added/",
+                "/added/dir/",
+                "/added/file.txt",
+                "/copy/",
+                "/copy/a.txt",
+                "/copy/nested/",
+                "/copy/nested/b.txt",
+                "/moved/",
+                "/moved/new.txt",
+                "/src/",
+                "/src/a.txt",
+                "/src/nested/",
+                "/src/nested/b.txt",
+            ],
+            fs.paths()
+        );
+        pretty_assertions::assert_eq!(Some(b"b".to_vec()), fs.read("/copy/nested/b.txt"));
+        pretty_assertions::assert_eq!(Some(b"old".to_vec()), fs.read("/moved/new.txt"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_rerun_applied_buffer() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b");
+
+        let input = r#"
+           abc   :   /a.txt
+           ecd   :   /b.txt
+COPY       abc   :   /c.txt
+DELETE     ecd   :   /b.txt
+"#;
+
+        apply_with(&fs, input, options()).await?;
+        apply_with(&fs, input, options()).await?;
+
+        pretty_assertions::assert_eq!(vec!["/", "/a.txt", "/c.txt"], fs.paths());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn move_fails_on_existing_destination() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b");
+
+        let input = r#"
+           abc   :   /a.txt
+MOVE       abc   :   /b.txt
+"#;
+
+        let res = apply_with(&fs, input, options()).await;
+
+        assert!(res.is_err());
+        pretty_assertions::assert_eq!(Some(b"b".to_vec()), fs.read("/b.txt"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_report_results() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b");
+
+        let input = r#"
+           abc   :   /a.txt
+ADD              :   /b.txt
+MOVE       abc   :   /b.txt
+ADD              :   /c.txt
+"#;
+
+        let report = apply_report(&fs, input, options()).await?;
+
+        assert!(!report.is_success());
+        pretty_assertions::assert_eq!(
+            vec![Status::Skipped, Status::Failed, Status::NotRun],
+            report.entries.iter().map(|e| e.status).collect::<Vec<_>>()
+        );
+        pretty_assertions::assert_eq!(
+            Some("MOVE /b.txt: destination already exists cannot move"),
+            report.entries[1].error.as_deref()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_overwrite_into_trash() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b")
+            .with_file("/c.txt", "c");
+
+        let input = r#"
+           abc   :   /a.txt
+           ecd   :   /c.txt
+MOVE       abc   :   /b.txt
+DELETE     ecd   :   /c.txt
+"#;
+
+        apply_with(
+            &fs,
+            input,
+            ApplyOptions {
+                conflict: Conflict::Overwrite,
+                trash: Some("/trash".into()),
+                ..options()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(
+            vec!["/", "/b.txt", "/trash/", "/trash/b.txt", "/trash/c.txt"],
+            fs.paths()
+        );
+        pretty_assertions::assert_eq!(Some(b"a".to_vec()), fs.read("/b.txt"));
+        pretty_assertions::assert_eq!(Some(b"b".to_vec()), fs.read("/trash/b.txt"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_run_commands() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().display();
+        std::fs::write(dir.path().join("a.txt"), "a")?;
+
+        let syntax = crate::syntax::Syntax::new(
+            [("DUP".to_string(), "TWICE".to_string())].into(),
+            [("TWICE".to_string(), "cat {src} {src} > {dst}".to_string())].into(),
+        )?;
+        let input = format!(
+            r#"
+           abc   :   {root}/a.txt
+DUP        abc   :   {root}/out/b.txt
+"#
+        );
+
+        let report = apply_report(
+            &crate::fs::RealFileSystem,
+            &input,
+            ApplyOptions {
+                syntax,
+                ..options()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(Some("TWICE"), report.entries[0].operation.as_deref());
+        pretty_assertions::assert_eq!("aa", std::fs::read_to_string(dir.path().join("out/b.txt"))?);
+
+        Ok(())
+    }
+}
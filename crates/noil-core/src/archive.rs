@@ -0,0 +1,574 @@
+use std::{
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+
+use crate::{
+    apply::{ApplyOptions, apply_report, apply_with},
+    fs::{FileKind, FileSystem, MemoryFileSystem, WalkOptions},
+    list_with, render_with,
+    report::Report,
+};
+
+/// The root archive members are listed below, such that a buffer for an archive reads
+/// `./dir/file`
+pub const ARCHIVE_ROOT: &str = ".";
+
+/// An archive which can be edited as a buffer.
+///
+/// The members are loaded into a [`MemoryFileSystem`], which the buffer is listed from and applied
+/// to, after which the archive is written back from it. Permission bits are kept, and so are the
+/// modification times of members which weren't created or copied, those get the time the archive
+/// is written. The archive itself keeps its permissions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Archive {
+    pub path: PathBuf,
+    pub format: ArchiveFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detects the format from the file extension, `None` if it isn't an archive
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+impl Archive {
+    /// Returns `None` if `path` isn't a file with an archive extension
+    pub fn detect(path: &Path) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+
+        Some(Self {
+            path: path.to_path_buf(),
+            format: ArchiveFormat::detect(path)?,
+        })
+    }
+
+    /// Reads the members of the archive into memory
+    pub async fn load(&self) -> anyhow::Result<MemoryFileSystem> {
+        let archive = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&archive.path).context("open archive")?;
+
+            match archive.format {
+                ArchiveFormat::Tar => load_tar(file),
+                ArchiveFormat::TarGz => load_tar(flate2::read::GzDecoder::new(file)),
+                ArchiveFormat::Zip => load_zip(file),
+            }
+        })
+        .await
+        .context("load archive task")?
+        .with_context(|| format!("load archive: {}", self.path.display()))
+    }
+
+    /// Lists the members of the archive as a buffer
+    pub async fn render(&self, no_color: bool) -> anyhow::Result<String> {
+        let fs = self.load().await?;
+
+        render_with(&fs, Path::new(ARCHIVE_ROOT), no_color).await
+    }
+
+    /// Lists the members of the archive as a report, see [`crate::report`]
+    pub async fn list(&self) -> anyhow::Result<Report> {
+        let fs = self.load().await?;
+
+        list_with(&fs, Path::new(ARCHIVE_ROOT)).await
+    }
+
+    /// Applies a buffer listed by [`Archive::render`] to the members, and writes the archive back.
+    /// Nothing is written if applying fails. `OPEN` entries have nothing to open on disk, as such
+    /// the chooser file is ignored, and neither is there a trash within the archive. Members can't
+    /// leave the archive, as such operations aren't confined either. The guard checks members
+    /// relative to the archive root, see [`crate::guard::Guard::within`].
+    pub async fn apply(&self, input: &str, options: ApplyOptions) -> anyhow::Result<()> {
+        let fs = self.load().await?;
+
+        apply_with(
+            &fs,
+            input,
+            ApplyOptions {
+                chooser_file: None,
+                trash: None,
+                confine: None,
+                guard: options.guard.within(ARCHIVE_ROOT),
+                ..options
+            },
+        )
+        .await?;
+
+        self.save(&fs).await
+    }
+
+    /// Same as [`Archive::apply`], but describes the result of every operation. The archive is
+    /// only written back if every operation was applied.
+    pub async fn apply_report(&self, input: &str, options: ApplyOptions) -> anyhow::Result<Report> {
+        let fs = self.load().await?;
+
+        let report = apply_report(
+            &fs,
+            input,
+            ApplyOptions {
+                chooser_file: None,
+                trash: None,
+                confine: None,
+                guard: options.guard.within(ARCHIVE_ROOT),
+                ..options
+            },
+        )
+        .await?;
+
+        if report.is_success() {
+            self.save(&fs).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Replaces the archive with the contents of `fs`. The new archive is written next to the old
+    /// one, and then moved in place, such that a failure doesn't leave a half written archive.
+    pub async fn save(&self, fs: &MemoryFileSystem) -> anyhow::Result<()> {
+        let now = SystemTime::now();
+        let mut members = Vec::new();
+        for entry in fs
+            .walk(Path::new(ARCHIVE_ROOT), WalkOptions::default())
+            .await?
+        {
+            let name = member_name(&entry.path);
+            if name.is_empty() {
+                continue;
+            }
+
+            let modified = fs
+                .stat(&entry.path)
+                .await?
+                .and_then(|m| m.modified)
+                .unwrap_or(now);
+            match entry.kind {
+                FileKind::Dir => members.push(Member::Dir { name, modified }),
+                FileKind::File | FileKind::Symlink => members.push(Member::File {
+                    name,
+                    contents: fs.read(&entry.path).unwrap_or_default(),
+                    mode: fs.mode(&entry.path).unwrap_or(0o644),
+                    modified,
+                }),
+            }
+        }
+
+        let archive = self.clone();
+        let tmp_path = self.path.with_file_name(format!(
+            ".{}.noil-tmp",
+            self.path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default()
+        ));
+
+        let tmp = tmp_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::create(&tmp).context("create archive")?;
+
+            match archive.format {
+                ArchiveFormat::Tar => {
+                    save_tar(file, &members)?;
+                }
+                ArchiveFormat::TarGz => {
+                    let encoder =
+                        flate2::write::GzEncoder::new(file, flate2::Compression::default());
+                    save_tar(encoder, &members)?.finish()?;
+                }
+                ArchiveFormat::Zip => save_zip(file, &members)?,
+            }
+
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .context("save archive task")?
+        .with_context(|| format!("save archive: {}", self.path.display()))?;
+
+        match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => tokio::fs::set_permissions(&tmp_path, metadata.permissions())
+                .await
+                .context("copy archive permissions")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("read archive permissions"),
+        }
+
+        tokio::fs::rename(&tmp_path, &self.path)
+            .await
+            .context("replace archive")?;
+
+        Ok(())
+    }
+}
+
+enum Member {
+    Dir {
+        name: String,
+        modified: SystemTime,
+    },
+    File {
+        name: String,
+        contents: Vec<u8>,
+        mode: u32,
+        modified: SystemTime,
+    },
+}
+
+/// The name of a member inside the archive, `./dir/file` is stored as `dir/file`
+fn member_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(c) => Some(c.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Member names are untrusted, anything trying to escape the root is refused
+fn member_path(name: &str) -> anyhow::Result<PathBuf> {
+    let mut path = PathBuf::from(ARCHIVE_ROOT);
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            _ => anyhow::bail!("archive member escapes the archive: {name}"),
+        }
+    }
+
+    Ok(path)
+}
+
+fn load_tar(reader: impl Read) -> anyhow::Result<MemoryFileSystem> {
+    let mut fs = MemoryFileSystem::new().with_dir(ARCHIVE_ROOT);
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries().context("read tar entries")? {
+        let mut entry = entry.context("read tar entry")?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let path = member_path(&name)?;
+        let modified = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => fs = fs.with_dir(&path),
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mode = entry.header().mode().unwrap_or(0o644);
+                let mut contents = Vec::new();
+                entry
+                    .read_to_end(&mut contents)
+                    .with_context(|| format!("read tar member: {name}"))?;
+
+                fs = fs.with_file_mode(&path, contents, mode);
+            }
+            // Metadata for the entry after it, handled by the tar crate
+            tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => continue,
+            other => anyhow::bail!(
+                "tar member: {name} is a {other:?}, only files and directories can be edited"
+            ),
+        }
+
+        if let Some(modified) = modified {
+            fs = fs.with_modified(&path, modified);
+        }
+    }
+
+    Ok(fs)
+}
+
+fn save_tar<W: Write>(writer: W, members: &[Member]) -> anyhow::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    let mtime = |modified: &SystemTime| {
+        modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    };
+
+    for member in members {
+        let mut header = tar::Header::new_gnu();
+
+        match member {
+            Member::Dir { name, modified } => {
+                header.set_mtime(mtime(modified));
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, format!("{name}/"), std::io::empty())?;
+            }
+            Member::File {
+                name,
+                contents,
+                mode,
+                modified,
+            } => {
+                header.set_mtime(mtime(modified));
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(*mode);
+                header.set_size(contents.len() as u64);
+                builder.append_data(&mut header, name, contents.as_slice())?;
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+fn load_zip(reader: impl Read + std::io::Seek) -> anyhow::Result<MemoryFileSystem> {
+    let mut fs = MemoryFileSystem::new().with_dir(ARCHIVE_ROOT);
+    let mut archive = zip::ZipArchive::new(reader).context("read zip")?;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).context("read zip member")?;
+        let name = file.name().to_string();
+        let path = member_path(&name)?;
+
+        if file.is_symlink() {
+            anyhow::bail!(
+                "zip member: {name} is a symlink, only files and directories can be edited"
+            );
+        }
+
+        let modified = file.last_modified().and_then(from_zip_time);
+
+        if file.is_dir() {
+            fs = fs.with_dir(&path);
+        } else {
+            let mode = file.unix_mode().map(|m| m & 0o7777).unwrap_or(0o644);
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)
+                .with_context(|| format!("read zip member: {name}"))?;
+
+            fs = fs.with_file_mode(&path, contents, mode);
+        }
+
+        if let Some(modified) = modified {
+            fs = fs.with_modified(&path, modified);
+        }
+    }
+
+    Ok(fs)
+}
+
+fn save_zip(writer: impl Write + std::io::Seek, members: &[Member]) -> anyhow::Result<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+
+    for member in members {
+        match member {
+            Member::Dir { name, modified } => {
+                zip.add_directory(
+                    format!("{name}/"),
+                    zip::write::SimpleFileOptions::default()
+                        .unix_permissions(0o755)
+                        .last_modified_time(to_zip_time(modified)),
+                )?;
+            }
+            Member::File {
+                name,
+                contents,
+                mode,
+                modified,
+            } => {
+                zip.start_file(
+                    name,
+                    zip::write::SimpleFileOptions::default()
+                        .compression_method(zip::CompressionMethod::Deflated)
+                        .unix_permissions(*mode)
+                        .last_modified_time(to_zip_time(modified)),
+                )?;
+                zip.write_all(contents)?;
+            }
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Zip times have no time zone, they are read and written as UTC such that they round trip
+fn from_zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    let time = chrono::NaiveDateTime::try_from(time).ok()?;
+    Some(time.and_utc().into())
+}
+
+/// Times outside what zip can store, 1980 to 2107, are written as the earliest it can
+fn to_zip_time(time: &SystemTime) -> zip::DateTime {
+    let time = chrono::DateTime::<chrono::Utc>::from(*time).naive_utc();
+    zip::DateTime::try_from(time).unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{
+        ApplyOptions,
+        archive::{Archive, ArchiveFormat},
+        fs::FileSystem,
+        guard::Guard,
+    };
+
+    async fn roundtrip(format: ArchiveFormat, name: &str) -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = Archive {
+            path: dir.path().join(name),
+            format,
+        };
+
+        let past = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        let fs = crate::fs::MemoryFileSystem::new()
+            .with_file_mode("./bin/run.sh", "#!/bin/sh", 0o755)
+            .with_file("./README.md", "readme")
+            .with_file("./docs/old.md", "old")
+            .with_modified("./bin/run.sh", past)
+            .with_modified("./docs/old.md", past);
+        archive.save(&fs).await?;
+        std::fs::set_permissions(&archive.path, std::fs::Permissions::from_mode(0o600))?;
+
+        let listing = archive.render(true).await?;
+        let tag = |path: &str| -> String {
+            listing
+                .lines()
+                .find(|l| l.rsplit_once(" : ").map(|(_, p)| p.trim()) == Some(path))
+                .and_then(|l| l.split_whitespace().next())
+                .expect("tag for path")
+                .to_string()
+        };
+
+        let input = format!(
+            "{listing}\nMOVE {} : ./docs/new.md\nDELETE {} : ./README.md\nCOPY {} : ./bin/copy.sh\n",
+            tag("./docs/old.md"),
+            tag("./README.md"),
+            tag("./bin/run.sh"),
+        );
+        archive
+            .apply(
+                &input,
+                ApplyOptions {
+                    quiet: true,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let fs = archive.load().await?;
+        pretty_assertions::assert_eq!(
+            vec!["bin/", "bin/copy.sh", "bin/run.sh", "docs/", "docs/new.md"],
+            fs.paths()
+        );
+        pretty_assertions::assert_eq!(Some(0o755), fs.mode("./bin/copy.sh"));
+        pretty_assertions::assert_eq!(Some(b"old".to_vec()), fs.read("./docs/new.md"));
+
+        let modified = |path: &'static str| {
+            let fs = &fs;
+            async move { fs.stat(Path::new(path)).await.ok().flatten()?.modified }
+        };
+        pretty_assertions::assert_eq!(Some(past), modified("./bin/run.sh").await);
+        pretty_assertions::assert_eq!(Some(past), modified("./docs/new.md").await);
+        assert!(modified("./bin/copy.sh").await > Some(past));
+        pretty_assertions::assert_eq!(
+            0o600,
+            std::fs::metadata(&archive.path)?.permissions().mode() & 0o777
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_edit_tar_gz() -> anyhow::Result<()> {
+        roundtrip(ArchiveFormat::TarGz, "release.tar.gz").await
+    }
+
+    #[tokio::test]
+    async fn can_edit_zip() -> anyhow::Result<()> {
+        roundtrip(ArchiveFormat::Zip, "release.zip").await
+    }
+
+    #[tokio::test]
+    async fn guards_members_relative_to_the_archive() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let archive = Archive {
+            path: dir.path().join("release.tar"),
+            format: ArchiveFormat::Tar,
+        };
+        archive
+            .save(
+                &crate::fs::MemoryFileSystem::new()
+                    .with_file("./a.txt", "a")
+                    .with_file("./b.txt", "b"),
+            )
+            .await?;
+
+        // Members aren't below the current directory, even though `./a.txt` would be on disk
+        let cwd = std::env::current_dir()?.display().to_string();
+        let apply = |input: &'static str, max_deletes: Option<usize>| {
+            let archive = &archive;
+            let cwd = &cwd;
+            async move {
+                archive
+                    .apply(
+                        input,
+                        ApplyOptions {
+                            quiet: true,
+                            guard: Guard::new(std::slice::from_ref(cwd), max_deletes, false)?,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+            }
+        };
+
+        let both = "abc : ./a.txt\necd : ./b.txt\nDELETE abc : ./a.txt\nDELETE ecd : ./b.txt";
+        assert!(apply(both, Some(1)).await.is_err());
+        apply("abc : ./a.txt\nDELETE abc : ./a.txt", Some(1)).await?;
+
+        pretty_assertions::assert_eq!(vec!["b.txt"], archive.load().await?.paths());
+
+        Ok(())
+    }
+
+    #[test]
+    fn can_detect_format() {
+        pretty_assertions::assert_eq!(
+            Some(ArchiveFormat::TarGz),
+            ArchiveFormat::detect(Path::new("release.tgz"))
+        );
+        pretty_assertions::assert_eq!(
+            Some(ArchiveFormat::Tar),
+            ArchiveFormat::detect(Path::new("release.TAR"))
+        );
+        pretty_assertions::assert_eq!(None, ArchiveFormat::detect(Path::new("notes.md")));
+    }
+}
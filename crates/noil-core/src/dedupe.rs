@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    format,
+    fs::{FileKind, FileSystem},
+    render::{self, Listed},
+};
+
+/// What to do with the duplicates of a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Delete every copy but one
+    #[default]
+    Delete,
+    /// Replace every copy but one with a hard link to it
+    Hardlink,
+}
+
+/// A listed file and its inode, see [`crate::fs::Metadata::inode`]
+type Candidate<'a> = (&'a Listed, Option<(u64, u64)>);
+
+/// Finds files below `root` with the same contents, and generates a buffer proposing to delete
+/// or hardlink all but one copy in each group.
+///
+/// The first copy in path order is kept, and listed without an operation, followed by its
+/// duplicates. Empty files and symlinks are skipped. Files are only hashed if another file has
+/// the same size, and hard links to the same file are only hashed once. Files which are already
+/// hard links to each other aren't duplicates, neither are they proposed to be linked again.
+pub async fn dedupe_with(
+    fs: &dyn FileSystem,
+    root: &Path,
+    mode: DedupeMode,
+) -> anyhow::Result<String> {
+    let (listed, _) = render::list(fs, root).await?;
+
+    let mut by_len: BTreeMap<u64, Vec<Candidate>> = BTreeMap::new();
+    for entry in listed.iter().filter(|e| e.kind == FileKind::File) {
+        let Some(metadata) = fs.stat(&entry.path).await? else {
+            continue;
+        };
+        if metadata.len == 0 {
+            continue;
+        }
+
+        by_len
+            .entry(metadata.len)
+            .or_default()
+            .push((entry, metadata.inode));
+    }
+
+    let mut groups: BTreeMap<(u64, [u8; 32]), Vec<Candidate>> = BTreeMap::new();
+    for (len, entries) in by_len {
+        if entries.len() < 2 {
+            continue;
+        }
+
+        // Hard links share their contents, as such each inode is hashed once
+        let mut hashed: BTreeMap<(u64, u64), [u8; 32]> = BTreeMap::new();
+        for (entry, inode) in entries {
+            let hash = match inode.and_then(|inode| hashed.get(&inode)) {
+                Some(hash) => *hash,
+                None => {
+                    let hash = fs.hash_file(&entry.path).await?;
+                    if let Some(inode) = inode {
+                        hashed.insert(inode, hash);
+                    }
+                    hash
+                }
+            };
+            groups.entry((len, hash)).or_default().push((entry, inode));
+        }
+    }
+
+    let mut groups = groups
+        .into_values()
+        .filter(|g| g.len() > 1)
+        // Already hard links to one file
+        .filter(|g| g[0].1.is_none() || g.iter().any(|(_, inode)| *inode != g[0].1))
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| a[0].0.path.cmp(&b[0].0.path));
+
+    let mut lines = Vec::new();
+    for group in groups {
+        let ((keep, keep_inode), duplicates) = group.split_first().expect("group to have entries");
+        lines.push(format!("{} : {}", keep.tag, keep.path.display()));
+
+        for (duplicate, inode) in duplicates {
+            if mode == DedupeMode::Hardlink && inode.is_some() && inode == keep_inode {
+                continue;
+            }
+
+            lines.push(match mode {
+                DedupeMode::Delete => {
+                    format!("DELETE {} : {}", duplicate.tag, duplicate.path.display())
+                }
+                DedupeMode::Hardlink => {
+                    format!("HARDLINK {} : {}", keep.tag, duplicate.path.display())
+                }
+            });
+        }
+    }
+
+    tracing::debug!(lines = lines.len(), "found duplicates");
+
+    format::format(&lines.join("\n"))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::{
+        apply::{ApplyOptions, apply_with},
+        fs::{MemoryFileSystem, RealFileSystem},
+    };
+
+    use super::{DedupeMode, dedupe_with};
+
+    fn fs() -> MemoryFileSystem {
+        MemoryFileSystem::new()
+            .with_file("/src/a.txt", "same")
+            .with_file("/src/b/a.txt", "same")
+            .with_file("/src/c.txt", "same")
+            .with_file("/src/d.txt", "diff")
+            .with_file("/src/e.txt", "")
+            .with_file("/src/f.txt", "")
+    }
+
+    #[tokio::test]
+    async fn can_delete_duplicates() -> anyhow::Result<()> {
+        let fs = fs();
+
+        let buffer = dedupe_with(&fs, Path::new("/src"), DedupeMode::Delete).await?;
+        pretty_assertions::assert_eq!(
+            vec!["", "DELETE", "DELETE"],
+            buffer
+                .lines()
+                .map(|l| l.split_whitespace().next().unwrap_or_default())
+                .map(|op| if op == "DELETE" { op } else { "" })
+                .collect::<Vec<_>>()
+        );
+
+        apply_with(
+            &fs,
+            &buffer,
+            ApplyOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(
+            vec![
+                "/",
+                "/src/",
+                "/src/a.txt",
+                "/src/b/",
+                "/src/d.txt",
+                "/src/e.txt",
+                "/src/f.txt"
+            ],
+            fs.paths()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_hardlink_duplicates() -> anyhow::Result<()> {
+        let fs = fs();
+
+        let buffer = dedupe_with(&fs, Path::new("/src"), DedupeMode::Hardlink).await?;
+        apply_with(
+            &fs,
+            &buffer,
+            ApplyOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(2, buffer.matches("HARDLINK").count());
+        pretty_assertions::assert_eq!(Some(b"same".to_vec()), fs.read("/src/c.txt"));
+        pretty_assertions::assert_eq!(None, fs.read("/src/.c.txt.noil-link"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skips_files_which_are_already_linked() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.txt"), "same")?;
+        std::fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt"))?;
+        std::fs::write(dir.path().join("c.txt"), "same")?;
+
+        let buffer = dedupe_with(&RealFileSystem, dir.path(), DedupeMode::Hardlink).await?;
+        pretty_assertions::assert_eq!(1, buffer.matches("HARDLINK").count());
+        assert!(buffer.contains(&format!("{}", dir.path().join("c.txt").display())));
+
+        apply_with(
+            &RealFileSystem,
+            &buffer,
+            ApplyOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(
+            "",
+            dedupe_with(&RealFileSystem, dir.path(), DedupeMode::Hardlink).await?
+        );
+
+        Ok(())
+    }
+}
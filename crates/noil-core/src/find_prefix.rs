@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+/// The shortest prefix of every value which no other value starts with, and the shortest length
+/// at which every prefix is unique. The values can be in any order, the prefixes are returned in
+/// the same order.
+pub(crate) fn shortest_unique_prefixes(values: &[&str]) -> (usize, Vec<String>, Vec<String>) {
+    if values.is_empty() {
+        return (0, Vec::new(), Vec::new());
+    }
+
+    let len = values[0].len();
+    // Duplicates are never unique, they get the whole value
+    let mut lens = vec![len; values.len()];
+
+    // Grows the prefixes one character at a time. A value whose prefix is unique is done, and
+    // can't collide with the longer prefixes of the values which aren't.
+    let mut pending = (0..values.len()).collect::<Vec<_>>();
+    for prefix_len in 1..=len {
+        if pending.is_empty() {
+            break;
+        }
+
+        let mut counts = HashMap::<&str, usize>::with_capacity(pending.len());
+        for &i in &pending {
+            *counts.entry(&values[i][..prefix_len]).or_default() += 1;
+        }
+
+        pending.retain(|&i| {
+            let unique = counts[&values[i][..prefix_len]] == 1;
+            if unique {
+                lens[i] = prefix_len;
+            }
+            !unique
+        });
+    }
+
+    // A single value needs no tag to tell it apart
+    let global_prefix_len = if values.len() == 1 {
+        0
+    } else {
+        lens.iter().copied().max().unwrap_or_default()
+    };
+
+    let global_prefixes = values
+        .iter()
+        .map(|s| s[..global_prefix_len].to_string())
+        .collect();
+    let individual_prefixes = values
+        .iter()
+        .zip(lens)
+        .map(|(s, len)| s[..len].to_string())
+        .collect();
+
+    (global_prefix_len, global_prefixes, individual_prefixes)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::find_prefix::shortest_unique_prefixes;
+
+    #[test]
+    fn simple_shortest() {
+        let mut input = vec!["1ab", "3ab", "1ca"];
+        let expected_len: usize = 2;
+        let expected_global: Vec<String> = vec!["1a".into(), "1c".into(), "3a".into()];
+        let expected_individual: Vec<String> = vec!["1a".into(), "1c".into(), "3".into()];
+
+        input.sort();
+
+        let (len, global_prefixes, individual_prefixes) = shortest_unique_prefixes(&input);
+
+        assert_eq!(expected_len, len);
+        assert_eq!(expected_global, global_prefixes);
+        assert_eq!(expected_individual, individual_prefixes);
+    }
+
+    #[test]
+    fn order_does_not_matter() {
+        let input = vec!["3ab", "1ca", "1ab", "1ab"];
+
+        let (len, global_prefixes, individual_prefixes) = shortest_unique_prefixes(&input);
+
+        assert_eq!(3, len);
+        assert_eq!(vec!["3ab", "1ca", "1ab", "1ab"], global_prefixes);
+        assert_eq!(vec!["3", "1c", "1ab", "1ab"], individual_prefixes);
+    }
+}
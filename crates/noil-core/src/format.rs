@@ -22,8 +22,9 @@ pub(crate) fn format(input: &str) -> anyhow::Result<String> {
             models::Operation::Copy { index }
             | models::Operation::Delete { index }
             | models::Operation::Move { index }
+            | models::Operation::Link { index }
             | models::Operation::Existing { index } => index.len(),
-            models::Operation::Open { index } => {
+            models::Operation::Open { index } | models::Operation::Command { index, .. } => {
                 index.as_ref().map(|i| i.len()).unwrap_or_default()
             }
             models::Operation::Add => 0,
@@ -32,8 +33,21 @@ pub(crate) fn format(input: &str) -> anyhow::Result<String> {
         .unwrap_or_default();
 
     let mut output_buf = Vec::new();
+    let mut files = noil_index.files.into_iter();
+
+    for line in input.lines().map(str::trim) {
+        // Comments are kept in place, such as operations skipped in interactive mode
+        if line.starts_with('#') {
+            output_buf.push(line.to_string());
+            continue;
+        }
+        if line.rsplit_once(" : ").is_none() {
+            continue;
+        }
+        let Some(file) = files.next() else {
+            break;
+        };
 
-    for file in noil_index.files {
         let mut line = String::new();
         let space = " ";
 
@@ -55,8 +69,9 @@ pub(crate) fn format(input: &str) -> anyhow::Result<String> {
             models::Operation::Copy { index }
             | models::Operation::Delete { index }
             | models::Operation::Move { index }
+            | models::Operation::Link { index }
             | models::Operation::Existing { index } => Some(index),
-            models::Operation::Open { index } => index,
+            models::Operation::Open { index } | models::Operation::Command { index, .. } => index,
             models::Operation::Add => None,
         };
 
@@ -117,6 +132,33 @@ MOVE   asdf23      :   /bla/bla/bla
         Ok(())
     }
 
+    #[test]
+    fn keeps_comments_in_place() -> anyhow::Result<()> {
+        let input = r#"
+# a comment
+a : /a
+  # MOVE a : /b
+COPY a :    /c
+# root: /
+"#;
+
+        let expected = r#"
+# a comment
+       a   :   /a
+# MOVE a : /b
+COPY   a   :   /c
+# root: /
+        "#
+        .trim();
+
+        let output = super::format(input)?;
+
+        pretty_assertions::assert_eq!(expected, &output);
+        pretty_assertions::assert_eq!(expected, &super::format(&output)?);
+
+        Ok(())
+    }
+
     #[test]
     fn can_format_no_op() -> anyhow::Result<()> {
         let input = r#"
@@ -0,0 +1,127 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+
+use crate::progress::Progress;
+
+mod filtered;
+mod git;
+mod memory;
+mod real;
+
+pub use filtered::FilteredFileSystem;
+pub use git::GitFileSystem;
+pub use memory::MemoryFileSystem;
+pub use real::RealFileSystem;
+
+/// The filesystem operations noil needs for listing and applying buffers.
+///
+/// [`RealFileSystem`] works on the disk, [`MemoryFileSystem`] keeps everything in memory, which is
+/// useful for tests and dry runs. Paths are given as written in the buffer, it is up to the
+/// implementation to resolve them.
+#[async_trait]
+pub trait FileSystem: Send + Sync {
+    /// Lists `root` and everything below it, sorted by path, such that parents come before their
+    /// children.
+    async fn walk(&self, root: &Path, options: WalkOptions) -> anyhow::Result<Vec<WalkEntry>>;
+
+    /// Returns `None` if nothing exists at `path`. Symlinks are followed.
+    async fn stat(&self, path: &Path) -> anyhow::Result<Option<Metadata>>;
+
+    /// Creates a directory and any missing parents
+    async fn create_dir_all(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Creates an empty file, truncating it if it already exists
+    async fn create_file(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Copies a single file, reporting the bytes copied to `progress`
+    async fn copy_file(&self, src: &Path, dest: &Path, progress: &Progress) -> anyhow::Result<()>;
+
+    async fn rename(&self, src: &Path, dest: &Path) -> anyhow::Result<()>;
+
+    async fn remove_file(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Removes an empty directory
+    async fn remove_dir(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Removes a directory and everything below it
+    async fn remove_dir_all(&self, path: &Path) -> anyhow::Result<()>;
+
+    /// Creates a hard link at `dest` pointing to `src`
+    async fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<()>;
+
+    /// Hashes the contents of a file with blake3
+    async fn hash_file(&self, path: &Path) -> anyhow::Result<[u8; 32]>;
+
+    /// Runs a shell command for a user defined operation, see [`crate::syntax`]. Filesystems which
+    /// aren't on disk can't run commands.
+    async fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        anyhow::bail!("can't run {command}, commands only run on disk")
+    }
+
+    /// Resolves `path` to an absolute path without `.`, `..` or symlinks, such that it can be
+    /// compared to other paths. Nothing has to exist at `path`.
+    async fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        Ok(normalize(path))
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        matches!(self.stat(path).await, Ok(Some(_)))
+    }
+
+    async fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.stat(path).await, Ok(Some(m)) if m.kind == FileKind::Dir)
+    }
+}
+
+/// Resolves `.` and `..` without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    /// Only reported by [`FileSystem::walk`], which doesn't follow symlinks
+    Symlink,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: FileKind,
+    /// Size in bytes, zero for directories
+    pub len: u64,
+    /// `None` if the filesystem doesn't keep modification times
+    pub modified: Option<std::time::SystemTime>,
+    /// The device and inode, hard links to the same file share them. `None` if the filesystem
+    /// has no inodes.
+    pub inode: Option<(u64, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// The hash the tag of the entry is taken from, if the walk was asked to hash
+    pub hash: Option<String>,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct WalkOptions {
+    /// Skip hidden files, and files ignored by `.gitignore` and `.ignore` files
+    pub respect_ignore: bool,
+    /// Hash the path of every entry as it is walked, see [`WalkEntry::hash`]
+    pub hash: bool,
+}
@@ -0,0 +1,189 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::{
+    fs::{FileSystem, Metadata, WalkEntry, WalkOptions},
+    progress::Progress,
+};
+
+/// Leaves paths matching a set of globs out of walks which respect ignore files, on top of what
+/// the inner filesystem already ignores.
+///
+/// Globs work like lines of a `.gitignore`: a glob without a `/` matches the name of a file or
+/// directory at any depth, such as `*.log` or `node_modules`, otherwise it matches the path
+/// relative to the walked root, such as `build/*.o`. Everything below an ignored directory is
+/// ignored too. Every other operation goes straight to the inner filesystem.
+pub struct FilteredFileSystem {
+    inner: Box<dyn FileSystem>,
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl FilteredFileSystem {
+    pub fn new(inner: Box<dyn FileSystem>, ignore: &[String]) -> anyhow::Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+
+        for glob in ignore {
+            let pattern = glob.trim_end_matches('/');
+            let (builder, pattern) = match pattern.strip_prefix('/') {
+                Some(pattern) => (&mut paths, pattern),
+                None if pattern.contains('/') => (&mut paths, pattern),
+                None => (&mut names, pattern),
+            };
+
+            builder.add(
+                GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid ignore glob: {glob}"))?,
+            );
+        }
+
+        Ok(Self {
+            inner,
+            names: names.build()?,
+            paths: paths.build()?,
+        })
+    }
+
+    fn is_ignored(&self, relative: &Path) -> bool {
+        let mut prefix = std::path::PathBuf::new();
+        for component in relative.components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            prefix.push(name);
+
+            if self.names.is_match(name) || self.paths.is_match(&prefix) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[async_trait]
+impl FileSystem for FilteredFileSystem {
+    async fn walk(&self, root: &Path, options: WalkOptions) -> anyhow::Result<Vec<WalkEntry>> {
+        let mut entries = self.inner.walk(root, options).await?;
+        if options.respect_ignore {
+            entries.retain(|e| match e.path.strip_prefix(root) {
+                Ok(relative) => !self.is_ignored(relative),
+                Err(_) => true,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    async fn stat(&self, path: &Path) -> anyhow::Result<Option<Metadata>> {
+        self.inner.stat(path).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.create_dir_all(path).await
+    }
+
+    async fn create_file(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.create_file(path).await
+    }
+
+    async fn copy_file(&self, src: &Path, dest: &Path, progress: &Progress) -> anyhow::Result<()> {
+        self.inner.copy_file(src, dest, progress).await
+    }
+
+    async fn rename(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        self.inner.rename(src, dest).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir_all(path).await
+    }
+
+    async fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        self.inner.link(src, dest).await
+    }
+
+    async fn hash_file(&self, path: &Path) -> anyhow::Result<[u8; 32]> {
+        self.inner.hash_file(path).await
+    }
+
+    async fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        self.inner.canonicalize(path).await
+    }
+
+    async fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        self.inner.run_command(command).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::fs::{FileSystem, MemoryFileSystem, WalkOptions};
+
+    use super::FilteredFileSystem;
+
+    #[tokio::test]
+    async fn leaves_out_ignored_paths() -> anyhow::Result<()> {
+        let fs = FilteredFileSystem::new(
+            Box::new(
+                MemoryFileSystem::new()
+                    .with_file("/src/a.txt", "a")
+                    .with_file("/src/a.log", "a")
+                    .with_file("/src/node_modules/b/c.js", "c")
+                    .with_file("/src/build/d.o", "d")
+                    .with_file("/src/lib/build/e.o", "e"),
+            ),
+            &["*.log".into(), "node_modules/".into(), "build/*.o".into()],
+        )?;
+
+        let walk = |respect_ignore| {
+            let fs = &fs;
+            async move {
+                anyhow::Ok(
+                    fs.walk(
+                        Path::new("/src"),
+                        WalkOptions {
+                            respect_ignore,
+                            ..Default::default()
+                        },
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|e| e.path.display().to_string())
+                    .collect::<Vec<_>>(),
+                )
+            }
+        };
+
+        pretty_assertions::assert_eq!(
+            vec![
+                "/src",
+                "/src/a.txt",
+                "/src/build",
+                "/src/lib",
+                "/src/lib/build",
+                "/src/lib/build/e.o"
+            ],
+            walk(true).await?
+        );
+        pretty_assertions::assert_eq!(11, walk(false).await?.len());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::{
+    fs::{FileSystem, Metadata, RealFileSystem, WalkEntry, WalkOptions},
+    progress::Progress,
+};
+
+/// The filesystem on disk, keeping the git index of a work tree up to date.
+///
+/// Moves of tracked paths go through `git mv`, and deletes through `git rm`, such that renames
+/// show up as renames in history instead of a delete and an untracked file. Untracked paths, and
+/// paths outside the work tree, are handled like [`RealFileSystem`] would.
+#[derive(Clone, Debug)]
+pub struct GitFileSystem {
+    /// The top level directory of the work tree
+    root: PathBuf,
+    /// Stage files created by `ADD` and `COPY`
+    stage_new: bool,
+}
+
+impl GitFileSystem {
+    /// Finds the work tree containing `path`, fails if it isn't inside one
+    pub async fn discover(path: &Path, stage_new: bool) -> anyhow::Result<Self> {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            }
+        };
+
+        let output = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["rev-parse", "--show-toplevel"])
+            .output()
+            .await
+            .context("run git, is it installed?")?;
+        if !output.status.success() {
+            anyhow::bail!("{} is not inside a git work tree", dir.display());
+        }
+
+        let root = String::from_utf8(output.stdout).context("git work tree is not utf8")?;
+
+        Ok(Self {
+            root: PathBuf::from(root.trim_end_matches('\n')),
+            stage_new,
+        })
+    }
+
+    /// The top level directory of the work tree
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves `path` to an absolute path, as git is run from the root of the work tree.
+    ///
+    /// The parent is canonicalized rather than the path itself, since the path doesn't exist yet
+    /// for the destination of a move.
+    fn absolute(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let path = std::path::absolute(path)?;
+
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => match parent.canonicalize() {
+                Ok(parent) => Ok(parent.join(name)),
+                Err(_) => Ok(path),
+            },
+            _ => Ok(path),
+        }
+    }
+
+    fn in_work_tree(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    async fn git(&self, args: &[&std::ffi::OsStr]) -> anyhow::Result<String> {
+        let output = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(args)
+            .output()
+            .await
+            .context("run git")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.first()
+                    .map(|a| a.to_string_lossy())
+                    .unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Whether git tracks `path`, or anything below it
+    async fn is_tracked(&self, path: &Path) -> anyhow::Result<bool> {
+        if !self.in_work_tree(path) {
+            return Ok(false);
+        }
+
+        let files = self
+            .git(&["ls-files".as_ref(), "--".as_ref(), path.as_os_str()])
+            .await?;
+
+        Ok(!files.is_empty())
+    }
+
+    /// Removes `path` from the index and the disk, anything untracked is left on disk
+    async fn remove(&self, path: &Path) -> anyhow::Result<()> {
+        let path = self.absolute(path)?;
+        if !self.is_tracked(&path).await? {
+            return Ok(());
+        }
+
+        tracing::debug!(path = %path.display(), "git rm");
+        self.git(&[
+            "rm".as_ref(),
+            "-r".as_ref(),
+            "-f".as_ref(),
+            "-q".as_ref(),
+            "--".as_ref(),
+            path.as_os_str(),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    async fn stage(&self, path: &Path) -> anyhow::Result<()> {
+        let path = self.absolute(path)?;
+        if !self.stage_new || !self.in_work_tree(&path) {
+            return Ok(());
+        }
+
+        tracing::debug!(path = %path.display(), "git add");
+        self.git(&["add".as_ref(), "--".as_ref(), path.as_os_str()])
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FileSystem for GitFileSystem {
+    async fn walk(&self, root: &Path, options: WalkOptions) -> anyhow::Result<Vec<WalkEntry>> {
+        RealFileSystem.walk(root, options).await
+    }
+
+    async fn stat(&self, path: &Path) -> anyhow::Result<Option<Metadata>> {
+        RealFileSystem.stat(path).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        RealFileSystem.create_dir_all(path).await
+    }
+
+    async fn create_file(&self, path: &Path) -> anyhow::Result<()> {
+        RealFileSystem.create_file(path).await?;
+        self.stage(path).await.context("stage new file")
+    }
+
+    async fn copy_file(&self, src: &Path, dest: &Path, progress: &Progress) -> anyhow::Result<()> {
+        RealFileSystem.copy_file(src, dest, progress).await?;
+        self.stage(dest).await.context("stage copied file")
+    }
+
+    async fn rename(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        let abs_src = self.absolute(src)?;
+        let abs_dest = self.absolute(dest)?;
+
+        if !self.in_work_tree(&abs_dest) {
+            // git can't move a path out of the work tree, the source is removed from the index
+            // instead
+            RealFileSystem.rename(src, dest).await?;
+            if self.is_tracked(&abs_src).await? {
+                self.git(&[
+                    "rm".as_ref(),
+                    "-r".as_ref(),
+                    "-q".as_ref(),
+                    "--cached".as_ref(),
+                    "--".as_ref(),
+                    abs_src.as_os_str(),
+                ])
+                .await?;
+            }
+            return Ok(());
+        }
+
+        if !self.is_tracked(&abs_src).await? {
+            return RealFileSystem.rename(src, dest).await;
+        }
+
+        tracing::debug!(src = %abs_src.display(), dest = %abs_dest.display(), "git mv");
+        self.git(&[
+            "mv".as_ref(),
+            // Only replaces the destination when applying a hardlink, moves check for it
+            // beforehand
+            "-f".as_ref(),
+            "--".as_ref(),
+            abs_src.as_os_str(),
+            abs_dest.as_os_str(),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> anyhow::Result<()> {
+        self.remove(path).await?;
+        if RealFileSystem.exists(path).await {
+            RealFileSystem.remove_file(path).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path) -> anyhow::Result<()> {
+        // git doesn't track directories, only the files within them
+        RealFileSystem.remove_dir(path).await
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        self.remove(path).await?;
+        if RealFileSystem.exists(path).await {
+            RealFileSystem.remove_dir_all(path).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        RealFileSystem.link(src, dest).await?;
+        self.stage(dest).await.context("stage linked file")
+    }
+
+    async fn hash_file(&self, path: &Path) -> anyhow::Result<[u8; 32]> {
+        RealFileSystem.hash_file(path).await
+    }
+
+    async fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        RealFileSystem.canonicalize(path).await
+    }
+
+    async fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        RealFileSystem.run_command(command).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::apply::{ApplyOptions, apply_with};
+
+    use super::GitFileSystem;
+
+    fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=noil", "-c", "user.email=noil@example.com"])
+            .args(args)
+            .output()?;
+        anyhow::ensure!(
+            output.status.success(),
+            "git {args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    #[tokio::test]
+    async fn moves_and_deletes_update_the_index() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        git(root, &["init", "-q"])?;
+        std::fs::create_dir(root.join("src"))?;
+        std::fs::write(root.join("src/a.txt"), "a")?;
+        std::fs::write(root.join("b.txt"), "b")?;
+        std::fs::write(root.join("untracked.txt"), "c")?;
+        git(root, &["add", "src", "b.txt"])?;
+        git(root, &["commit", "-q", "-m", "init"])?;
+
+        let fs = GitFileSystem::discover(root, true).await?;
+        let root = root.display();
+        let input = format!(
+            r#"
+a          : {root}/src/
+b          : {root}/b.txt
+c          : {root}/untracked.txt
+MOVE a     : {root}/lib/
+DELETE b   : {root}/b.txt
+MOVE c     : {root}/moved.txt
+ADD        : {root}/new.txt
+"#
+        );
+
+        apply_with(&fs, &input, ApplyOptions::default()).await?;
+
+        let status = git(dir.path(), &["status", "--porcelain"])?;
+        let mut status = status.lines().collect::<Vec<_>>();
+        status.sort();
+
+        pretty_assertions::assert_eq!(
+            vec![
+                "?? moved.txt",
+                "A  new.txt",
+                "D  b.txt",
+                "R  src/a.txt -> lib/a.txt"
+            ],
+            status
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_outside_a_work_tree() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        assert!(GitFileSystem::discover(dir.path(), false).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    fs::{FileKind, FileSystem, Metadata, WalkEntry, WalkOptions},
+    progress::Progress,
+    render::hash_path,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    /// The modification time, see [`MemoryFileSystem::with_modified`]
+    Dir(Option<SystemTime>),
+    File(FileNode),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct FileNode {
+    contents: Vec<u8>,
+    /// Unix permission bits, kept so that archives can be written back without losing them
+    mode: u32,
+    modified: Option<SystemTime>,
+}
+
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// A filesystem kept entirely in memory.
+///
+/// Paths are normalized lexically, `./a/b/` and `a/b` are the same entry. The in-memory
+/// filesystem has no inodes, as such a link is a copy of the file, and has no symlinks.
+///
+/// Entries only have a modification time if it is set with [`MemoryFileSystem::with_modified`].
+/// Moving an entry keeps it, created and copied files have none.
+#[derive(Default, Debug)]
+pub struct MemoryFileSystem {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with `contents`, creating its parents
+    pub fn with_file(self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.with_file_mode(path, contents, DEFAULT_FILE_MODE)
+    }
+
+    /// Adds a file with `contents` and the unix permission bits `mode`, creating its parents
+    pub fn with_file_mode(
+        self,
+        path: impl AsRef<Path>,
+        contents: impl Into<Vec<u8>>,
+        mode: u32,
+    ) -> Self {
+        {
+            let mut nodes = self.lock();
+            let path = normalize(path.as_ref());
+            insert_parents(&mut nodes, &path);
+            nodes.insert(
+                path,
+                Node::File(FileNode {
+                    contents: contents.into(),
+                    mode,
+                    modified: None,
+                }),
+            );
+        }
+        self
+    }
+
+    /// Adds a directory, creating its parents
+    pub fn with_dir(self, path: impl AsRef<Path>) -> Self {
+        {
+            let mut nodes = self.lock();
+            let path = normalize(path.as_ref());
+            insert_parents(&mut nodes, &path);
+            nodes.insert(path, Node::Dir(None));
+        }
+        self
+    }
+
+    /// Sets the modification time of an existing file or directory
+    pub fn with_modified(self, path: impl AsRef<Path>, time: SystemTime) -> Self {
+        match self.lock().get_mut(&normalize(path.as_ref())) {
+            Some(Node::Dir(modified)) => *modified = Some(time),
+            Some(Node::File(file)) => file.modified = Some(time),
+            None => {}
+        }
+        self
+    }
+
+    /// The contents of a file, `None` if it doesn't exist or is a directory
+    pub fn read(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        match self.lock().get(&normalize(path.as_ref())) {
+            Some(Node::File(file)) => Some(file.contents.clone()),
+            _ => None,
+        }
+    }
+
+    /// The unix permission bits of a file, `None` if it doesn't exist or is a directory
+    pub fn mode(&self, path: impl AsRef<Path>) -> Option<u32> {
+        match self.lock().get(&normalize(path.as_ref())) {
+            Some(Node::File(file)) => Some(file.mode),
+            _ => None,
+        }
+    }
+
+    /// Every path in the filesystem, sorted, directories end with a `/`
+    pub fn paths(&self) -> Vec<String> {
+        self.lock()
+            .iter()
+            .filter(|(path, _)| !path.as_os_str().is_empty())
+            .map(|(path, node)| {
+                let path = path.display().to_string();
+                match node {
+                    Node::Dir(_) if !path.ends_with('/') => format!("{path}/"),
+                    _ => path,
+                }
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes
+            .lock()
+            .expect("memory filesystem lock to not be poisoned")
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+fn insert_parents(nodes: &mut BTreeMap<PathBuf, Node>, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+        nodes.entry(parent.to_path_buf()).or_insert(Node::Dir(None));
+    }
+}
+
+fn not_found(path: &Path) -> anyhow::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+    .into()
+}
+
+fn require_parent(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> anyhow::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => match nodes.get(parent) {
+            Some(Node::Dir(_)) => Ok(()),
+            Some(Node::File(_)) => anyhow::bail!("{} is not a directory", parent.display()),
+            None => Err(not_found(parent)),
+        },
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl FileSystem for MemoryFileSystem {
+    async fn walk(&self, root: &Path, options: WalkOptions) -> anyhow::Result<Vec<WalkEntry>> {
+        let nodes = self.lock();
+        let normalized_root = normalize(root);
+
+        if !nodes.contains_key(&normalized_root) {
+            return Err(not_found(root));
+        }
+
+        let mut entries = Vec::new();
+        for (path, node) in nodes.range(normalized_root.clone()..) {
+            let Ok(relative) = path.strip_prefix(&normalized_root) else {
+                break;
+            };
+
+            let hidden = relative
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+            if options.respect_ignore && hidden {
+                continue;
+            }
+
+            let path = if relative.as_os_str().is_empty() {
+                root.to_path_buf()
+            } else {
+                root.join(relative)
+            };
+            entries.push(WalkEntry {
+                hash: options.hash.then(|| hash_path(&path)),
+                path,
+                kind: match node {
+                    Node::Dir(_) => FileKind::Dir,
+                    Node::File(_) => FileKind::File,
+                },
+            });
+        }
+
+        Ok(entries)
+    }
+
+    async fn stat(&self, path: &Path) -> anyhow::Result<Option<Metadata>> {
+        Ok(self.lock().get(&normalize(path)).map(|node| match node {
+            Node::Dir(modified) => Metadata {
+                kind: FileKind::Dir,
+                len: 0,
+                modified: *modified,
+                inode: None,
+            },
+            Node::File(file) => Metadata {
+                kind: FileKind::File,
+                len: file.contents.len() as u64,
+                modified: file.modified,
+                inode: None,
+            },
+        }))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let path = normalize(path);
+
+        for dir in path.ancestors() {
+            if let Some(Node::File(_)) = nodes.get(dir) {
+                anyhow::bail!("{} is not a directory", dir.display());
+            }
+        }
+
+        insert_parents(&mut nodes, &path);
+        nodes.entry(path).or_insert(Node::Dir(None));
+
+        Ok(())
+    }
+
+    async fn create_file(&self, path: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let path = normalize(path);
+
+        require_parent(&nodes, &path)?;
+        if let Some(Node::Dir(_)) = nodes.get(&path) {
+            anyhow::bail!("{} is a directory", path.display());
+        }
+
+        nodes.insert(
+            path,
+            Node::File(FileNode {
+                contents: Vec::new(),
+                mode: DEFAULT_FILE_MODE,
+                modified: None,
+            }),
+        );
+
+        Ok(())
+    }
+
+    async fn copy_file(&self, src: &Path, dest: &Path, progress: &Progress) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let dest = normalize(dest);
+
+        let Some(Node::File(file)) = nodes.get(&normalize(src)).cloned() else {
+            return Err(not_found(src));
+        };
+        require_parent(&nodes, &dest)?;
+
+        progress.inc_bytes(file.contents.len() as u64);
+        nodes.insert(
+            dest,
+            Node::File(FileNode {
+                modified: None,
+                ..file
+            }),
+        );
+
+        Ok(())
+    }
+
+    async fn rename(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let src = normalize(src);
+        let dest = normalize(dest);
+
+        if !nodes.contains_key(&src) {
+            return Err(not_found(&src));
+        }
+        require_parent(&nodes, &dest)?;
+
+        let moved = nodes
+            .keys()
+            .filter(|p| p.starts_with(&src))
+            .cloned()
+            .collect::<Vec<_>>();
+        for path in moved {
+            let node = nodes.remove(&path).expect("node to exist");
+            let relative = path.strip_prefix(&src).expect("path to be below source");
+            let target = if relative.as_os_str().is_empty() {
+                dest.clone()
+            } else {
+                dest.join(relative)
+            };
+            nodes.insert(target, node);
+        }
+
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let path = normalize(path);
+
+        match nodes.get(&path) {
+            Some(Node::File(_)) => {
+                nodes.remove(&path);
+                Ok(())
+            }
+            Some(Node::Dir(_)) => anyhow::bail!("{} is a directory", path.display()),
+            None => Err(not_found(&path)),
+        }
+    }
+
+    async fn remove_dir(&self, path: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let path = normalize(path);
+
+        match nodes.get(&path) {
+            Some(Node::Dir(_)) => {
+                if nodes.keys().any(|p| p != &path && p.starts_with(&path)) {
+                    anyhow::bail!("{} is not empty", path.display());
+                }
+                nodes.remove(&path);
+                Ok(())
+            }
+            Some(Node::File(_)) => anyhow::bail!("{} is not a directory", path.display()),
+            None => Err(not_found(&path)),
+        }
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let path = normalize(path);
+
+        if !nodes.contains_key(&path) {
+            return Err(not_found(&path));
+        }
+        nodes.retain(|p, _| !p.starts_with(&path));
+
+        Ok(())
+    }
+
+    async fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        let mut nodes = self.lock();
+        let dest = normalize(dest);
+
+        let Some(Node::File(file)) = nodes.get(&normalize(src)).cloned() else {
+            return Err(not_found(src));
+        };
+        require_parent(&nodes, &dest)?;
+        if nodes.contains_key(&dest) {
+            anyhow::bail!("{} already exists", dest.display());
+        }
+
+        nodes.insert(dest, Node::File(file));
+
+        Ok(())
+    }
+
+    async fn hash_file(&self, path: &Path) -> anyhow::Result<[u8; 32]> {
+        match self.lock().get(&normalize(path)) {
+            Some(Node::File(file)) => Ok(*blake3::hash(&file.contents).as_bytes()),
+            Some(Node::Dir(_)) => anyhow::bail!("{} is a directory", path.display()),
+            None => Err(not_found(path)),
+        }
+    }
+}
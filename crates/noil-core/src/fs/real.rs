@@ -0,0 +1,310 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use ignore::WalkState;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    fs::{FileKind, FileSystem, Metadata, WalkEntry, WalkOptions, normalize},
+    progress::Progress,
+    render::hash_path,
+};
+
+/// The filesystem on disk
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RealFileSystem;
+
+#[async_trait]
+impl FileSystem for RealFileSystem {
+    async fn walk(&self, root: &Path, options: WalkOptions) -> anyhow::Result<Vec<WalkEntry>> {
+        let root = root.to_path_buf();
+        let mut entries = tokio::task::spawn_blocking(move || {
+            if options.respect_ignore {
+                walk_parallel(&root, options.hash)
+            } else {
+                walk_all(&root, options.hash)
+            }
+        })
+        .await
+        .context("walk task")??;
+
+        entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(entries)
+    }
+
+    async fn run_command(&self, command: &str) -> anyhow::Result<()> {
+        tracing::debug!(command, "running command");
+        let output = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .await
+            .context("run sh")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "command failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn canonicalize(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let absolute = std::path::absolute(path).context("absolute path")?;
+
+        // Symlinks are resolved one component at a time, such that `..` after a symlink leaves
+        // the directory the symlink points to, the same as it would for the kernel
+        let mut resolved = PathBuf::new();
+        for component in absolute.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                component => {
+                    resolved.push(component);
+
+                    let is_symlink = tokio::fs::symlink_metadata(&resolved)
+                        .await
+                        .is_ok_and(|m| m.is_symlink());
+                    if !is_symlink {
+                        continue;
+                    }
+
+                    resolved = match tokio::fs::canonicalize(&resolved).await {
+                        Ok(target) => target,
+                        // A dangling symlink, which would be written through
+                        Err(_) => {
+                            let target = tokio::fs::read_link(&resolved).await?;
+                            normalize(&resolved.parent().unwrap_or(Path::new("/")).join(target))
+                        }
+                    };
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    async fn stat(&self, path: &Path) -> anyhow::Result<Option<Metadata>> {
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => Ok(Some(to_metadata(&metadata))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(path).await?;
+        Ok(())
+    }
+
+    async fn create_file(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::File::create(path).await?;
+        Ok(())
+    }
+
+    /// The file is copied in chunks, so that large files report progress while being copied
+    /// instead of looking stuck. A hidden progress bar skips the updates.
+    async fn copy_file(&self, src: &Path, dest: &Path, progress: &Progress) -> anyhow::Result<()> {
+        let show_progress = !progress.is_hidden();
+
+        let mut reader = tokio::fs::File::open(src)
+            .await
+            .context("open source file")?;
+        let mut writer = tokio::fs::File::create(dest)
+            .await
+            .context("create destination file")?;
+
+        let mut buf = vec![0; 1024 * 1024];
+        loop {
+            let n = reader.read(&mut buf).await.context("read source file")?;
+            if n == 0 {
+                break;
+            }
+
+            writer
+                .write_all(&buf[..n])
+                .await
+                .context("write destination file")?;
+            if show_progress {
+                progress.inc_bytes(n as u64);
+            }
+        }
+        writer.flush().await.context("flush destination file")?;
+
+        let permissions = tokio::fs::metadata(src).await?.permissions();
+        tokio::fs::set_permissions(dest, permissions)
+            .await
+            .context("copy permissions")?;
+
+        Ok(())
+    }
+
+    async fn rename(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        tokio::fs::rename(src, dest).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::remove_file(path).await?;
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::remove_dir(path).await?;
+        Ok(())
+    }
+
+    async fn remove_dir_all(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::remove_dir_all(path).await?;
+        Ok(())
+    }
+
+    async fn link(&self, src: &Path, dest: &Path) -> anyhow::Result<()> {
+        tokio::fs::hard_link(src, dest).await?;
+        Ok(())
+    }
+
+    async fn hash_file(&self, path: &Path) -> anyhow::Result<[u8; 32]> {
+        let path = path.to_path_buf();
+
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&path)?;
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(file)?;
+
+            Ok(*hasher.finalize().as_bytes())
+        })
+        .await
+        .context("hash task")?
+    }
+}
+
+fn to_metadata(metadata: &std::fs::Metadata) -> Metadata {
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    };
+    #[cfg(not(unix))]
+    let inode = None;
+
+    if metadata.is_dir() {
+        Metadata {
+            kind: FileKind::Dir,
+            len: 0,
+            modified: metadata.modified().ok(),
+            inode,
+        }
+    } else {
+        Metadata {
+            kind: FileKind::File,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            inode,
+        }
+    }
+}
+
+fn to_kind(file_type: std::fs::FileType) -> FileKind {
+    if file_type.is_symlink() {
+        FileKind::Symlink
+    } else if file_type.is_dir() {
+        FileKind::Dir
+    } else {
+        FileKind::File
+    }
+}
+
+/// Walks everything below root
+fn walk_all(root: &Path, hash: bool) -> anyhow::Result<Vec<WalkEntry>> {
+    let mut entries = Vec::new();
+
+    for entry in walkdir::WalkDir::new(root) {
+        let entry = entry?;
+        let kind = to_kind(entry.file_type());
+
+        entries.push(WalkEntry {
+            hash: hash.then(|| hash_path(entry.path())),
+            path: entry.into_path(),
+            kind,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Walks the tree on all cores, skipping hidden and ignored files. With `hash` the entries are
+/// hashed by the visitors as they are walked. The entries are returned in no particular order.
+fn walk_parallel(root: &Path, hash: bool) -> anyhow::Result<Vec<WalkEntry>> {
+    let entries = Mutex::new(Vec::new());
+    let error = Mutex::new(None);
+
+    ignore::WalkBuilder::new(root)
+        .hidden(true)
+        .git_ignore(true)
+        .ignore(true)
+        .build_parallel()
+        .run(|| {
+            let error = &error;
+
+            // Flushes the thread local entries once the visitor is dropped at the end of the walk
+            struct Flush<'a> {
+                local: Vec<WalkEntry>,
+                entries: &'a Mutex<Vec<WalkEntry>>,
+            }
+            impl Drop for Flush<'_> {
+                fn drop(&mut self) {
+                    if let Ok(mut entries) = self.entries.lock() {
+                        entries.append(&mut self.local);
+                    }
+                }
+            }
+            let mut flush = Flush {
+                local: Vec::new(),
+                entries: &entries,
+            };
+
+            Box::new(move |entry| {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        if let Ok(mut error) = error.lock() {
+                            error.get_or_insert(e);
+                        }
+                        return WalkState::Quit;
+                    }
+                };
+
+                let kind = entry.file_type().map(to_kind).unwrap_or(FileKind::File);
+                flush.local.push(WalkEntry {
+                    hash: hash.then(|| hash_path(entry.path())),
+                    path: entry.into_path(),
+                    kind,
+                });
+
+                WalkState::Continue
+            })
+        });
+
+    if let Some(e) = error
+        .into_inner()
+        .expect("walk error lock to not be poisoned")
+    {
+        return Err(e.into());
+    }
+
+    Ok(entries
+        .into_inner()
+        .expect("walk entries lock to not be poisoned"))
+}
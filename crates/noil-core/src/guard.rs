@@ -0,0 +1,439 @@
+//! Checks a plan for destructive operations before anything is applied.
+//!
+//! Protected paths can't be deleted, moved away or written to, and neither can their parents be
+//! deleted or moved. `/`, `$HOME` and mount points are always protected, on top of the globs
+//! given to [`Guard::new`]. A glob protects everything below the paths it matches, and a leading
+//! `~` stands for `$HOME`.
+//!
+//! Deleting more files than the threshold fails as well, unless the guard is forced.
+//!
+//! Relative paths are made absolute from the current directory, or joined onto the root given to
+//! [`Guard::within`], and symlinks in their parents are resolved before they are checked.
+//!
+//! [`confine`] checks that a plan stays within a root directory.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::{
+    fs::{FileKind, FileSystem, WalkOptions, normalize},
+    models::Operation,
+    plan::Plan,
+};
+
+/// The default [`Guard`] only protects `/`, `$HOME` and mount points, without a threshold
+#[derive(Clone, Debug)]
+pub struct Guard {
+    /// Paths which can't be removed, nor can their parents
+    paths: Vec<PathBuf>,
+    globs: GlobSet,
+    /// The most files a plan may delete
+    max_deletes: Option<usize>,
+    /// Skip the threshold, protected paths are still checked
+    force: bool,
+    /// What relative paths are relative to, the current directory if unset
+    root: Option<PathBuf>,
+}
+
+impl Default for Guard {
+    fn default() -> Self {
+        Self {
+            paths: builtin_paths(home().as_deref()),
+            globs: GlobSet::empty(),
+            max_deletes: None,
+            force: false,
+            root: None,
+        }
+    }
+}
+
+impl Guard {
+    pub fn new(
+        protected: &[String],
+        max_deletes: Option<usize>,
+        force: bool,
+    ) -> anyhow::Result<Self> {
+        let home = home();
+
+        let mut globs = GlobSetBuilder::new();
+        for glob in protected {
+            let pattern = match (glob.strip_prefix('~'), &home) {
+                (Some(rest), Some(home)) => format!("{}{rest}", home.display()),
+                _ => glob.clone(),
+            };
+            let pattern = pattern.trim_end_matches('/');
+
+            globs.add(
+                GlobBuilder::new(if pattern.is_empty() { "/" } else { pattern })
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid protected glob: {glob}"))?,
+            );
+        }
+
+        Ok(Self {
+            paths: builtin_paths(home.as_deref()),
+            globs: globs.build()?,
+            max_deletes,
+            force,
+            root: None,
+        })
+    }
+
+    /// Resolves relative paths against `root` instead of the current directory, which is how
+    /// archive members are checked, see [`crate::archive::Archive::apply`]. The root isn't made
+    /// absolute, as such a relative root keeps the paths clear of the protected paths on disk,
+    /// while the threshold still applies.
+    pub fn within(self, root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: Some(root.into()),
+            ..self
+        }
+    }
+
+    /// Fails if the plan touches a protected path, or deletes too many files
+    pub async fn check(&self, fs: &dyn FileSystem, plan: &Plan) -> anyhow::Result<()> {
+        let mut violations = Vec::new();
+        let mut deleted = 0;
+
+        for step in &plan.steps {
+            let removed = match &step.operation {
+                Operation::Delete { .. } => Some(&step.path),
+                Operation::Move { .. } => step.source.as_ref(),
+                _ => None,
+            };
+            let written = match &step.operation {
+                Operation::Add
+                | Operation::Copy { .. }
+                | Operation::Move { .. }
+                | Operation::Link { .. }
+                | Operation::Command { .. } => Some(&step.path),
+                Operation::Delete { .. } | Operation::Existing { .. } | Operation::Open { .. } => {
+                    None
+                }
+            };
+
+            if let Some(path) = written
+                && self.is_protected(&self.resolve(fs, path).await?, true)
+            {
+                violations.push(format!(
+                    "{} {}: is protected",
+                    step.operation,
+                    path.display()
+                ));
+            }
+
+            let Some(removed) = removed else {
+                continue;
+            };
+            let path = self.resolve(fs, removed).await?;
+
+            let entries = fs
+                .walk(&path, WalkOptions::default())
+                .await
+                .unwrap_or_default();
+            if matches!(step.operation, Operation::Delete { .. }) {
+                deleted += entries.iter().filter(|e| e.kind != FileKind::Dir).count();
+            }
+
+            if let Some(protected) =
+                self.paths
+                    .iter()
+                    .find(|p| p.starts_with(&path))
+                    .or_else(|| {
+                        std::iter::once(&path)
+                            .chain(entries.iter().map(|e| &e.path))
+                            .find(|p| self.is_protected(p, false))
+                    })
+            {
+                violations.push(format!(
+                    "{} {}: {} is protected",
+                    step.operation,
+                    removed.display(),
+                    protected.display()
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_deletes
+            && deleted > max
+            && !self.force
+        {
+            violations.push(format!(
+                "{deleted} files would be deleted, which is more than {max}, use --force to delete them anyway"
+            ));
+        }
+
+        refuse(violations)
+    }
+
+    /// Whether `path`, or one of its parents, matches a protected glob. With `exact`, a path
+    /// which is always protected counts too.
+    fn is_protected(&self, path: &Path, exact: bool) -> bool {
+        (exact && self.paths.iter().any(|p| p == path))
+            || path.ancestors().any(|p| self.globs.is_match(p))
+    }
+
+    /// Makes `path` absolute, or joins it onto the root, and canonicalizes its parent. The last
+    /// component is kept as is, as removing a symlink removes the link rather than what it points
+    /// to.
+    async fn resolve(&self, fs: &dyn FileSystem, path: &Path) -> anyhow::Result<PathBuf> {
+        let absolute = match &self.root {
+            Some(root) => root.join(path),
+            None => std::path::absolute(path)
+                .with_context(|| format!("absolute path of {}", path.display()))?,
+        };
+
+        let resolved = match (absolute.parent(), absolute.file_name()) {
+            (Some(parent), Some(name)) => fs.canonicalize(parent).await.map(|p| p.join(name)),
+            // `/`, or a path ending in `..`
+            _ => fs.canonicalize(&absolute).await,
+        };
+
+        resolved.with_context(|| format!("canonicalize {}", path.display()))
+    }
+}
+
+/// Fails if a source or destination of the plan is outside `root`. Paths are canonicalized
+/// first, such that neither `..` nor symlinks lead out of the root.
+pub async fn confine(fs: &dyn FileSystem, root: &Path, plan: &Plan) -> anyhow::Result<()> {
+    let root = fs
+        .canonicalize(root)
+        .await
+        .with_context(|| format!("canonicalize root {}", root.display()))?;
+
+    let mut violations = Vec::new();
+    for step in &plan.steps {
+        for path in step.source.iter().chain(std::iter::once(&step.path)) {
+            let canonical = fs
+                .canonicalize(path)
+                .await
+                .with_context(|| format!("canonicalize {}", path.display()))?;
+
+            if !canonical.starts_with(&root) {
+                violations.push(format!(
+                    "{} {}: {} is outside {}",
+                    step.operation,
+                    step.path.display(),
+                    canonical.display(),
+                    root.display()
+                ));
+            }
+        }
+    }
+
+    refuse(violations)
+}
+
+fn refuse(violations: Vec<String>) -> anyhow::Result<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "refusing to apply, nothing has been changed:\n{}",
+        violations
+            .iter()
+            .map(|v| format!("  - {v}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `/`, `$HOME` and mount points
+fn builtin_paths(home: Option<&Path>) -> Vec<PathBuf> {
+    std::iter::once(PathBuf::from("/"))
+        .chain(home.map(Path::to_path_buf))
+        .chain(mount_points())
+        .map(|p| normalize(&p))
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn mount_points() -> Vec<PathBuf> {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return Vec::new();
+    };
+
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        // Spaces and the like are escaped as octal, such as `\040`
+        .map(|point| {
+            let mut path = String::new();
+            let mut rest = point;
+            while let Some(start) = rest.find('\\') {
+                path.push_str(&rest[..start]);
+                match u8::from_str_radix(rest.get(start + 1..start + 4).unwrap_or(""), 8) {
+                    Ok(byte) => {
+                        path.push(byte as char);
+                        rest = &rest[start + 4..];
+                    }
+                    Err(_) => {
+                        path.push('\\');
+                        rest = &rest[start + 1..];
+                    }
+                }
+            }
+            path.push_str(rest);
+            PathBuf::from(path)
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mount_points() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{
+        declared_root,
+        fs::{MemoryFileSystem, RealFileSystem},
+        parse, plan,
+    };
+
+    use super::{Guard, confine};
+
+    async fn check(guard: &Guard, buffer: &str) -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/srv/vault/secret.txt", "a")
+            .with_file("/srv/www/index.html", "b")
+            .with_file("/srv/www/style.css", "c")
+            .with_file("/srv/www/app.js", "d");
+
+        guard.check(&fs, &plan(&parse(buffer)?)).await
+    }
+
+    #[tokio::test]
+    async fn refuses_protected_paths() -> anyhow::Result<()> {
+        let guard = Guard::new(&["/srv/vault".into()], None, false)?;
+
+        assert!(check(&guard, "abc : /\nDELETE abc : /").await.is_err());
+        assert!(
+            check(&guard, "abc : /srv\nDELETE abc : /srv/www/../")
+                .await
+                .is_err()
+        );
+        assert!(
+            check(&guard, "abc : /srv/vault/secret.txt\nMOVE abc : /tmp/a")
+                .await
+                .is_err()
+        );
+        assert!(check(&guard, "ADD : /srv/vault/new.txt").await.is_err());
+        check(&guard, "abc : /srv/www\nDELETE abc : /srv/www").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_relative_paths() -> anyhow::Result<()> {
+        let cwd = std::env::current_dir()?;
+        let fs = MemoryFileSystem::new()
+            .with_file(cwd.join("vault/secret.txt"), "a")
+            .with_file(cwd.join("www/index.html"), "b");
+        let guard = Guard::new(&[cwd.join("vault").display().to_string()], None, false)?;
+        let check = |buffer: String| {
+            let guard = &guard;
+            let fs = &fs;
+            async move { guard.check(fs, &plan(&parse(&buffer)?)).await }
+        };
+
+        let root = "../".repeat(cwd.components().count());
+        for buffer in [
+            "abc : ./vault/secret.txt\nDELETE abc : ./vault/secret.txt".to_string(),
+            "abc : vault\nMOVE abc : /tmp/vault".to_string(),
+            "abc : www/../vault\nMOVE abc : /tmp/vault".to_string(),
+            "abc : .\nDELETE abc : .".to_string(),
+            format!("abc : {root}\nDELETE abc : {root}"),
+            "ADD : ./vault/new.txt".to_string(),
+        ] {
+            assert!(
+                check(buffer.clone()).await.is_err(),
+                "{buffer} to be refused"
+            );
+        }
+        check("abc : ./www\nDELETE abc : ./www".into()).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_protects_the_root() -> anyhow::Result<()> {
+        assert!(
+            check(&Guard::default(), "abc : /\nDELETE abc : /")
+                .await
+                .is_err()
+        );
+        check(&Guard::default(), "abc : /srv/www\nDELETE abc : /srv/www").await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_too_many_deletes_unless_forced() -> anyhow::Result<()> {
+        let buffer = "abc : /srv/www\nDELETE abc : /srv/www";
+
+        assert!(
+            check(&Guard::new(&[], Some(2), false)?, buffer)
+                .await
+                .is_err()
+        );
+        check(&Guard::new(&[], Some(3), false)?, buffer).await?;
+        check(&Guard::new(&[], Some(2), true)?, buffer).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn confines_to_the_root() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("a"))?;
+        std::fs::create_dir_all(dir.path().join("outside"))?;
+        std::os::unix::fs::symlink(dir.path().join("outside"), root.join("escape"))?;
+
+        let buffer = |lines: &[String]| {
+            format!(
+                "# root: {}\n# COPY abc : /etc\n{}",
+                root.display(),
+                lines.join("\n")
+            )
+        };
+        let check = |buffer: String| async move {
+            let root = declared_root(&buffer).expect("buffer to declare a root");
+            confine(&RealFileSystem, &root, &plan(&parse(&buffer)?)).await
+        };
+
+        let a = root.join("a");
+        check(buffer(&[
+            format!("abc : {}", a.display()),
+            format!("COPY abc : {}", root.join("b/./c").display()),
+        ]))
+        .await?;
+
+        for destination in [
+            root.join("../outside/b"),
+            root.join("escape/b"),
+            root.join("a/../../outside"),
+        ] {
+            let res = check(buffer(&[
+                format!("abc : {}", a.display()),
+                format!("COPY abc : {}", destination.display()),
+            ]))
+            .await;
+            assert!(res.is_err(), "{} to be refused", destination.display());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! Shell commands which run around applying a plan.
+//!
+//! - `pre_apply` runs before anything is applied, and gets the planned [`Report`] on stdin. A
+//!   failing hook aborts the apply.
+//! - `post_apply` runs after the plan, failed or not, and gets the applied [`Report`] on stdin.
+//! - `pre_operation` runs before every operation, and gets its [`ReportEntry`] on stdin. A
+//!   failing hook fails the operation, such that it isn't performed.
+//! - `post_operation` runs after every operation which was started, and gets its [`ReportEntry`]
+//!   on stdin, including the status. A failing hook fails the operation, even though it was
+//!   performed, such that the rest of the plan stops.
+//!
+//! Per operation hooks also get the entry as environment variables: `NOIL_OPERATION`,
+//! `NOIL_PATH`, `NOIL_SOURCE`, `NOIL_DESTINATION`, `NOIL_TAG`, `NOIL_STATUS` and `NOIL_ERROR`,
+//! variables without a value are left out. Every hook gets `NOIL_HOOK`, the name of the hook.
+
+use std::process::Stdio;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    plan::{Plan, Step},
+    report::{Report, ReportEntry, Status},
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    pub pre_apply: Vec<String>,
+    pub post_apply: Vec<String>,
+    pub pre_operation: Vec<String>,
+    pub post_operation: Vec<String>,
+}
+
+impl Hooks {
+    pub(crate) async fn pre_apply(&self, plan: &Plan) -> anyhow::Result<()> {
+        if self.pre_apply.is_empty() {
+            return Ok(());
+        }
+
+        run("pre_apply", &self.pre_apply, &Report::planned(plan), &[]).await
+    }
+
+    pub(crate) async fn post_apply(&self, report: &Report) -> anyhow::Result<()> {
+        run("post_apply", &self.post_apply, report, &[]).await
+    }
+
+    pub(crate) async fn pre_operation(&self, step: &Step) -> anyhow::Result<()> {
+        if self.pre_operation.is_empty() {
+            return Ok(());
+        }
+
+        let entry = ReportEntry::step(step, Status::Planned, None);
+        run("pre_operation", &self.pre_operation, &entry, &env(&entry)).await
+    }
+
+    pub(crate) async fn post_operation(
+        &self,
+        step: &Step,
+        res: &anyhow::Result<Status>,
+    ) -> anyhow::Result<()> {
+        if self.post_operation.is_empty() {
+            return Ok(());
+        }
+
+        let entry = match res {
+            Ok(status) => ReportEntry::step(step, *status, None),
+            Err(e) => ReportEntry::step(step, Status::Failed, Some(format!("{e:#}"))),
+        };
+        run("post_operation", &self.post_operation, &entry, &env(&entry)).await
+    }
+}
+
+fn env(entry: &ReportEntry) -> Vec<(&'static str, String)> {
+    let status = serde_json::to_value(entry.status)
+        .ok()
+        .and_then(|s| s.as_str().map(str::to_string));
+
+    [
+        ("NOIL_OPERATION", entry.operation.clone()),
+        ("NOIL_PATH", Some(entry.path.display().to_string())),
+        (
+            "NOIL_SOURCE",
+            entry.source.as_ref().map(|p| p.display().to_string()),
+        ),
+        (
+            "NOIL_DESTINATION",
+            entry.destination.as_ref().map(|p| p.display().to_string()),
+        ),
+        ("NOIL_TAG", entry.tag.clone()),
+        ("NOIL_STATUS", status),
+        ("NOIL_ERROR", entry.error.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect()
+}
+
+/// Runs every command of a hook in order with `input` as JSON on stdin, stopping at the first
+/// which fails. Their output goes to stderr, such that stdout is left for noil.
+async fn run(
+    hook: &str,
+    commands: &[String],
+    input: &impl Serialize,
+    env: &[(&str, String)],
+) -> anyhow::Result<()> {
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let input = serde_json::to_vec(input).context("serialize hook input")?;
+
+    for command in commands {
+        tracing::debug!(hook, command, "running hook");
+
+        let mut child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("NOIL_HOOK", hook)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("{hook} hook: start {command}"))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // A hook may well exit without reading its input
+            if let Err(e) = stdin.write_all(&input).await
+                && e.kind() != std::io::ErrorKind::BrokenPipe
+            {
+                return Err(e).with_context(|| format!("{hook} hook: write to {command}"));
+            }
+        }
+
+        let output = child
+            .wait_with_output()
+            .await
+            .with_context(|| format!("{hook} hook: wait for {command}"))?;
+        if !output.stdout.is_empty() {
+            eprint!("{}", String::from_utf8_lossy(&output.stdout));
+        }
+
+        if !output.status.success() {
+            anyhow::bail!("{hook} hook: {command} failed with {}", output.status);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::{
+        apply::ApplyOptions,
+        apply_report, apply_with,
+        fs::{FileSystem, MemoryFileSystem},
+    };
+
+    use super::Hooks;
+
+    #[tokio::test]
+    async fn pre_apply_hook_can_abort() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new();
+        let options = ApplyOptions {
+            quiet: true,
+            hooks: Hooks {
+                pre_apply: vec!["grep -q '\"operation\":\"DELETE\"' && exit 1 || exit 0".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        apply_with(&fs, "ADD : /a.txt", options.clone()).await?;
+        assert!(fs.exists(Path::new("/a.txt")).await);
+
+        let res = apply_with(&fs, "abc : /a.txt\nDELETE abc : /a.txt", options).await;
+        assert!(res.is_err());
+        assert!(fs.exists(Path::new("/a.txt")).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn operation_hooks_get_the_entry() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let log = dir.path().join("log");
+        let fs = MemoryFileSystem::new().with_file("/protected/a.txt", "a");
+
+        let options = ApplyOptions {
+            quiet: true,
+            hooks: Hooks {
+                pre_operation: vec![
+                    r#"case "$NOIL_OPERATION $NOIL_PATH" in "DELETE /protected/"*) exit 1;; esac"#
+                        .into(),
+                ],
+                post_operation: vec![format!(
+                    r#"echo "$NOIL_OPERATION $NOIL_PATH $NOIL_STATUS" >> '{}'"#,
+                    log.display()
+                )],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let report = apply_report(
+            &fs,
+            "ADD : /b.txt\nabc : /protected/a.txt\nDELETE abc : /protected/a.txt",
+            options,
+        )
+        .await?;
+
+        assert!(!report.is_success());
+        assert!(fs.exists(Path::new("/protected/a.txt")).await);
+        pretty_assertions::assert_eq!("ADD /b.txt applied\n", std::fs::read_to_string(&log)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Buffers generated from other formats.
+//!
+//! A plan can be given as JSON, using the same schema as a [`crate::report::Report`], such that
+//! the output of `noil --format json` can be edited by a script and fed back. Only `tag`, `path`,
+//! `operation` and `source` are read, every other key is ignored:
+//!
+//! ```json
+//! {
+//!   "entries": [
+//!     { "tag": "abc", "path": "/etc/nginx" },
+//!     { "operation": "COPY", "tag": "abc", "path": "/tmp/nginx-copy" },
+//!     { "operation": "MOVE", "source": "/tmp/a.txt", "path": "/tmp/b.txt" },
+//!     { "operation": "DELETE", "path": "/tmp/c.txt" },
+//!     { "operation": "ADD", "path": "/new/file.txt" }
+//!   ]
+//! }
+//! ```
+//!
+//! `COPY`, `MOVE` and `HARDLINK` refer to their source either by the `tag` of an entry without an
+//! operation, or by its `source` path directly. A `tag` which no entry without an operation
+//! declares is ignored when there is a `source`, such that a planned report can be applied as is.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::models::{Buffer, File, FileEntry, Operation};
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct PlanInput {
+    pub entries: Vec<PlanInputEntry>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct PlanInputEntry {
+    #[serde(default)]
+    pub tag: Option<String>,
+    pub path: PathBuf,
+    /// Any operation the text format accepts, such as `MOVE` or `MV`. `null` for an existing
+    /// entry.
+    #[serde(default)]
+    pub operation: Option<String>,
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+}
+
+/// Parses a JSON plan into a buffer
+pub fn from_json(input: &str) -> anyhow::Result<Buffer> {
+    let plan: PlanInput = serde_json::from_str(input).context("parse json plan")?;
+
+    let mut tags = Tags::new(&plan);
+    // Tags declared by entries without an operation, other tags can't be referred to, such as the
+    // ones of a planned report
+    let declared = plan
+        .entries
+        .iter()
+        .filter(|e| e.operation.as_deref().is_none_or(|op| op.trim().is_empty()))
+        .filter_map(|e| e.tag.as_deref())
+        .collect::<BTreeSet<_>>();
+    let mut files = Vec::with_capacity(plan.entries.len());
+
+    for (i, entry) in plan.entries.iter().enumerate() {
+        let op = entry.operation.as_deref().unwrap_or_default().trim();
+
+        if op.is_empty() {
+            let tag = entry
+                .tag
+                .as_ref()
+                .with_context(|| format!("entry {i}: an entry without an operation needs a tag"))?;
+
+            files.push(File {
+                path: entry.path.clone(),
+                entry: FileEntry {
+                    raw_op: None,
+                    operation: Operation::Existing { index: tag.clone() },
+                },
+            });
+            continue;
+        }
+
+        let tag = match (&entry.tag, &entry.source) {
+            (Some(tag), _) if declared.contains(tag.as_str()) => Some(tag.clone()),
+            (_, Some(source)) => {
+                // The source is listed as an existing entry, such that the operation can refer
+                // to it
+                let tag = tags.next();
+                files.push(File {
+                    path: source.clone(),
+                    entry: FileEntry {
+                        raw_op: None,
+                        operation: Operation::Existing { index: tag.clone() },
+                    },
+                });
+                Some(tag)
+            }
+            (tag, None) => tag.clone(),
+        };
+
+        // Parsed like a line of the text format, to accept the same operations. Operations which
+        // don't take a tag, such as ADD, ignore it, and deletes only need their path.
+        let file_entry = match &tag {
+            Some(tag) => FileEntry::parse(&format!("{op} {tag}")).or_else(|_| FileEntry::parse(op)),
+            None => {
+                FileEntry::parse(op).or_else(|_| FileEntry::parse(&format!("{op} {}", tags.next())))
+            }
+        }
+        .with_context(|| format!("entry {i}"))?;
+
+        match &file_entry.operation {
+            Operation::Copy { .. } | Operation::Move { .. } | Operation::Link { .. }
+                if tag.is_none() =>
+            {
+                anyhow::bail!("entry {i}: {op} needs either a tag or a source");
+            }
+            Operation::Existing { .. } => {
+                anyhow::bail!("entry {i}: operation: {op} is not supported");
+            }
+            _ => {}
+        }
+
+        files.push(File {
+            path: entry.path.clone(),
+            entry: file_entry,
+        });
+    }
+
+    Ok(Buffer { files })
+}
+
+/// Hands out tags which aren't used by the plan
+struct Tags {
+    used: BTreeSet<String>,
+    next: usize,
+}
+
+impl Tags {
+    fn new(plan: &PlanInput) -> Self {
+        Self {
+            used: plan.entries.iter().filter_map(|e| e.tag.clone()).collect(),
+            next: 0,
+        }
+    }
+
+    fn next(&mut self) -> String {
+        loop {
+            let tag = format!("src{}", self.next);
+            self.next += 1;
+
+            if self.used.insert(tag.clone()) {
+                return tag;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{
+        apply::{ApplyOptions, apply_with},
+        fs::MemoryFileSystem,
+        models::Operation,
+        parse::parse_input,
+        plan::{Step, plan},
+        report::Report,
+    };
+
+    #[test]
+    fn can_import_json_plan() -> anyhow::Result<()> {
+        let input = r#"
+{
+  "entries": [
+    { "tag": "src0", "path": "/var/a", "status": "listed" },
+    { "operation": "COPY", "tag": "src0", "path": "/tmp/a" },
+    { "operation": "MV", "source": "/var/b", "path": "/tmp/b" },
+    { "operation": "DELETE", "path": "/var/c" },
+    { "operation": "ADD", "path": "/tmp/d/" }
+  ]
+}
+"#;
+
+        let buffer = super::from_json(input)?;
+
+        pretty_assertions::assert_eq!(
+            "src0 : /var/a\nCOPY src0 : /tmp/a\nsrc1 : /var/b\nMOVE src1 : /tmp/b\nDELETE src2 : /var/c\nADD : /tmp/d/\n",
+            buffer.to_string()
+        );
+        pretty_assertions::assert_eq!(
+            Step {
+                operation: Operation::Move {
+                    index: "src1".into()
+                },
+                source: Some("/var/b".into()),
+                path: "/tmp/b".into(),
+            },
+            plan(&buffer).steps[1]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_apply_planned_report() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b")
+            .with_file("/c.txt", "c");
+
+        let input = r#"
+abc        : /a.txt
+ecd        : /b.txt
+fgh        : /c.txt
+COPY abc   : /copy.txt
+MOVE ecd   : /moved.txt
+DELETE fgh : /c.txt
+"#;
+        let report = Report::planned(&plan(&parse_input(input)?));
+
+        let buffer = super::from_json(&report.to_json()?)?;
+        apply_with(
+            &fs,
+            &buffer.to_string(),
+            ApplyOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        pretty_assertions::assert_eq!(vec!["/", "/a.txt", "/copy.txt", "/moved.txt"], fs.paths());
+        pretty_assertions::assert_eq!(Some(b"b".to_vec()), fs.read("/moved.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn copy_needs_a_source() {
+        let input = r#"{ "entries": [{ "operation": "COPY", "path": "/tmp/a" }] }"#;
+
+        assert!(super::from_json(input).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! The engine behind [noil](https://git.kjuulh.io/kjuulh/noil), a file explorer using text
+//! buffers.
+//!
+//! A noil buffer lists files with a tag, and optionally an operation to perform on them:
+//!
+//! ```text
+//!          abc   :   /etc/nginx
+//! COPY     abc   :   /tmp/nginx-copy
+//! DELETE   123   :   /etc/nginx
+//! ADD            :   /new/file.txt
+//! ```
+//!
+//! The crate covers the whole lifecycle of a buffer:
+//!
+//! - [`render`] lists a directory as a tagged buffer
+//! - [`parse`] turns a buffer into a [`Buffer`]
+//! - [`format()`] aligns the columns of a buffer
+//! - [`preview()`] describes the changes a buffer would make, [`preview_with`] in detail
+//! - [`plan()`] resolves the operations of a buffer into steps
+//! - [`apply()`] performs the operations of a buffer on the filesystem
+//!
+//! The listing, plan and applied operations can also be described as JSON, see [`report`], and
+//! buffers can be generated from JSON plans and path lists, see [`import`] and [`render_paths`].
+//!
+//! All filesystem access goes through the [`fs::FileSystem`] trait, the `*_with` variants take
+//! the filesystem to use, such as the in-memory [`fs::MemoryFileSystem`]. Tar and zip archives
+//! are edited by loading them into memory, see [`archive::Archive`].
+
+use std::path::{Path, PathBuf};
+
+pub mod apply;
+pub mod archive;
+pub mod dedupe;
+pub mod fs;
+pub mod guard;
+pub mod hooks;
+pub mod import;
+pub mod models;
+pub mod organize;
+pub mod plan;
+pub mod preview;
+pub mod progress;
+pub mod rename;
+pub mod report;
+pub mod sync;
+pub mod syntax;
+pub mod template;
+
+mod encode_rand;
+mod find_prefix;
+mod format;
+mod parse;
+mod render;
+
+pub use apply::{ApplyOptions, apply, apply_report, apply_with};
+pub use models::{Buffer, File, FileEntry, Operation};
+pub use plan::{Plan, Step, plan};
+pub use preview::{preview, preview_with};
+
+/// Parses a buffer. Lines without a ` : ` divider, and lines starting with `#`, are ignored.
+/// Only built-in operations are accepted, see [`parse_with`] for aliases and user defined ones.
+pub fn parse(input: &str) -> anyhow::Result<Buffer> {
+    parse_with(input, &syntax::Syntax::default())
+}
+
+/// Parses a buffer, resolving its operations against `syntax`
+pub fn parse_with(input: &str, syntax: &syntax::Syntax) -> anyhow::Result<Buffer> {
+    syntax.resolve(parse::parse_input(input)?)
+}
+
+/// The root directory a buffer declares with a `# root: <path>` line
+pub fn declared_root(input: &str) -> Option<PathBuf> {
+    parse::declared_root(input)
+}
+
+/// Aligns the operations, tags and paths of a buffer into columns
+pub fn format(input: &str) -> anyhow::Result<String> {
+    format_with(input, &syntax::Syntax::default())
+}
+
+/// Formats a buffer, refusing operations which `syntax` doesn't know. Aliases are kept as written.
+pub fn format_with(input: &str, syntax: &syntax::Syntax) -> anyhow::Result<String> {
+    parse_with(input, syntax)?;
+    format::format(input)
+}
+
+/// Lists `path` and everything below it as a buffer, each entry tagged with the shortest unique
+/// prefix of the hash of its path. Hidden and ignored files are left out.
+pub async fn render(path: &Path, no_color: bool) -> anyhow::Result<String> {
+    render::render(&fs::RealFileSystem, path, no_color).await
+}
+
+/// Same as [`render`], but lists the given filesystem
+pub async fn render_with(
+    fs: &dyn fs::FileSystem,
+    path: &Path,
+    no_color: bool,
+) -> anyhow::Result<String> {
+    render::render(fs, path, no_color).await
+}
+
+/// Same as [`render`], but describes the listing as a [`report::Report`]
+pub async fn list(path: &Path) -> anyhow::Result<report::Report> {
+    render::report(&fs::RealFileSystem, path).await
+}
+
+/// Same as [`list`], but lists the given filesystem
+pub async fn list_with(fs: &dyn fs::FileSystem, path: &Path) -> anyhow::Result<report::Report> {
+    render::report(fs, path).await
+}
+
+/// Lists the given paths as a buffer, tagged the same way as [`render`] would. The paths are
+/// sorted and deduplicated, nothing below them is listed.
+pub async fn render_paths(paths: &[PathBuf], no_color: bool) -> anyhow::Result<String> {
+    render::render_paths(&fs::RealFileSystem, paths, no_color).await
+}
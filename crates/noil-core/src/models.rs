@@ -4,7 +4,7 @@ use std::path::PathBuf;
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Buffer {
-    pub(crate) files: Vec<File>,
+    pub files: Vec<File>,
 }
 
 impl Buffer {
@@ -18,24 +18,45 @@ impl Buffer {
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct File {
-    pub(crate) path: PathBuf,
-    pub(crate) entry: FileEntry,
+    pub path: PathBuf,
+    pub entry: FileEntry,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct FileEntry {
-    pub(crate) raw_op: Option<String>,
-    pub(crate) operation: Operation,
+    /// The operation as written in the buffer, such as `MV` for a move
+    pub raw_op: Option<String>,
+    pub operation: Operation,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Operation {
-    Existing { index: String },
+    Existing {
+        index: String,
+    },
     Add,
-    Copy { index: String },
-    Delete { index: String },
-    Move { index: String },
-    Open { index: Option<String> },
+    Copy {
+        index: String,
+    },
+    Delete {
+        index: String,
+    },
+    Move {
+        index: String,
+    },
+    /// Replaces the path with a hard link to the tagged file
+    Link {
+        index: String,
+    },
+    Open {
+        index: Option<String>,
+    },
+    /// An operation which isn't built in, such as `COMPRESS`, run as a command configured in a
+    /// [`crate::syntax::Syntax`]. The tag is optional, and refers to the input of the command.
+    Command {
+        name: String,
+        index: Option<String>,
+    },
 }
 
 impl Display for Operation {
@@ -46,14 +67,52 @@ impl Display for Operation {
             Operation::Copy { .. } => "COPY",
             Operation::Delete { .. } => "DELETE",
             Operation::Move { .. } => "MOVE",
+            Operation::Link { .. } => "HARDLINK",
             Operation::Open { .. } => "OPEN",
+            Operation::Command { name, .. } => name,
         };
 
         f.write_str(op)
     }
 }
 
+/// Writes the buffer in the text format, one entry per line. The columns aren't aligned, see
+/// [`crate::format()`].
+impl Display for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in &self.files {
+            writeln!(f, "{file}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = &self.entry.operation;
+        let path = self.path.display();
+
+        match op {
+            Operation::Existing { index } => write!(f, "{index} : {path}"),
+            Operation::Copy { index }
+            | Operation::Delete { index }
+            | Operation::Move { index }
+            | Operation::Link { index } => write!(f, "{op} {index} : {path}"),
+            Operation::Open { index: Some(index) }
+            | Operation::Command {
+                index: Some(index), ..
+            } => write!(f, "{op} {index} : {path}"),
+            Operation::Add
+            | Operation::Open { index: None }
+            | Operation::Command { index: None, .. } => write!(f, "{op} : {path}"),
+        }
+    }
+}
+
 impl FileEntry {
+    /// Parses the left side of a line. Operations which aren't built in are parsed as
+    /// [`Operation::Command`], see [`crate::syntax::Syntax::resolve`] for what they refer to.
     pub(crate) fn parse(file_entry: &str) -> anyhow::Result<Self> {
         let items = file_entry.split(' ').collect::<Vec<_>>();
 
@@ -88,6 +147,8 @@ impl FileEntry {
             "D" | "DEL" | "DELETE" if first != last => Operation::Delete { index },
             // MOVE:
             "M" | "MV" | "MOVE" | "RENAME" if first != last => Operation::Move { index },
+            // HARDLINK:
+            "L" | "LN" | "LINK" | "HARDLINK" if first != last => Operation::Link { index },
             "O" | "OPEN" => Operation::Open {
                 index: {
                     // if LAST == is the Operation, we set the index to empty, if the index is missing we set it to None
@@ -98,6 +159,10 @@ impl FileEntry {
                     }
                 },
             },
+            o if is_operation_name(o) && !BUILTIN_OPERATIONS.contains(&o) => Operation::Command {
+                name: o.to_string(),
+                index: (first != last).then_some(index),
+            },
             o => {
                 anyhow::bail!("operation: {} is not supported", o);
             }
@@ -110,6 +175,20 @@ impl FileEntry {
     }
 }
 
+/// The built in operations, along with their short forms
+pub(crate) const BUILTIN_OPERATIONS: &[&str] = &[
+    "A", "ADD", "C", "COPY", "D", "DEL", "DELETE", "M", "MV", "MOVE", "RENAME", "L", "LN", "LINK",
+    "HARDLINK", "O", "OPEN",
+];
+
+/// Whether `name` can be the name of an operation, such as `COMPRESS` or `GZIP-9`
+pub(crate) fn is_operation_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use crate::{models::*, parse};
@@ -442,4 +521,42 @@ RENAME ecd : /var/my/some-different-place
 
         Ok(())
     }
+
+    #[test]
+    fn can_parse_item_link_operation() -> anyhow::Result<()> {
+        let input = r#"
+LN abc          : /var/my
+HARDLINK ecd    : /var/my/path
+"#;
+
+        let output = parse::parse_input(input)?;
+
+        pretty_assertions::assert_eq!(
+            Buffer {
+                files: vec![
+                    File {
+                        path: "/var/my".into(),
+                        entry: FileEntry {
+                            raw_op: Some("LN".into()),
+                            operation: Operation::Link {
+                                index: "abc".into()
+                            }
+                        },
+                    },
+                    File {
+                        path: "/var/my/path".into(),
+                        entry: FileEntry {
+                            raw_op: Some("HARDLINK".into()),
+                            operation: Operation::Link {
+                                index: "ecd".into()
+                            }
+                        },
+                    },
+                ]
+            },
+            output
+        );
+
+        Ok(())
+    }
 }
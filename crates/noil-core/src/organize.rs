@@ -0,0 +1,268 @@
+//! Sorts files into folders by rules.
+//!
+//! Rules are read from TOML, every rule moves the files matching its glob into a folder below
+//! the root. The folder is a [`crate::template`], and the first matching rule wins:
+//!
+//! ```toml
+//! [[rule]]
+//! glob = "*.{jpg,jpeg,png}"
+//! folder = "photos/{mtime:%Y}"
+//!
+//! [[rule]]
+//! glob = "scan_*.pdf"
+//! folder = "scans"
+//! ```
+//!
+//! Globs are matched case insensitively against the path relative to the root, files which
+//! match no rule are left alone.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::Deserialize;
+
+use crate::{
+    format,
+    fs::FileSystem,
+    render,
+    template::{Template, TemplateContext},
+};
+
+#[derive(Clone, Debug)]
+pub struct Organize {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    /// `None` matches every file
+    glob: Option<GlobMatcher>,
+    folder: Template,
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize)]
+struct RuleEntry {
+    glob: String,
+    folder: String,
+}
+
+impl Organize {
+    /// `report.PDF` into `pdf/`, files without an extension are left alone
+    pub fn by_extension() -> Self {
+        Self::folder("{ext:lower}")
+    }
+
+    /// `report.pdf` into `2026/10/` by its modification time
+    pub fn by_modified() -> Self {
+        Self::folder("{mtime:%Y}/{mtime:%m}")
+    }
+
+    fn folder(template: &str) -> Self {
+        Self {
+            rules: vec![Rule {
+                glob: None,
+                folder: Template::parse(template).expect("builtin template to be valid"),
+            }],
+        }
+    }
+
+    pub fn from_toml(input: &str) -> anyhow::Result<Self> {
+        let file: RulesFile = toml::from_str(input).context("parse rules")?;
+        if file.rules.is_empty() {
+            anyhow::bail!("no rules found, add them as [[rule]] with a glob and a folder");
+        }
+
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|rule| {
+                let glob = GlobBuilder::new(&rule.glob)
+                    .case_insensitive(true)
+                    .build()
+                    .with_context(|| format!("invalid glob: {}", rule.glob))?
+                    .compile_matcher();
+                let folder = Template::parse(&rule.folder)
+                    .with_context(|| format!("invalid folder for {}", rule.glob))?;
+
+                Ok(Rule {
+                    glob: Some(glob),
+                    folder,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// The folder `path` belongs in, `None` if no rule matches or the folder is empty
+    fn folder_for(
+        &self,
+        relative: &Path,
+        context: &TemplateContext,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|r| r.glob.as_ref().is_none_or(|g| g.is_match(relative)))
+        else {
+            return Ok(None);
+        };
+
+        let folder = rule
+            .folder
+            .render(context)
+            .with_context(|| format!("organize {}", context.path.display()))?;
+        let folder = folder.trim_matches('/');
+
+        Ok((!folder.is_empty()).then(|| folder.to_string()))
+    }
+}
+
+/// Generates a buffer moving the files in `root` into the folders `organize` sorts them in.
+///
+/// Only the files directly in `root` are sorted, unless `recursive` is set. Files already in
+/// their folder are left out of the buffer.
+pub async fn organize_with(
+    fs: &dyn FileSystem,
+    root: &Path,
+    organize: &Organize,
+    recursive: bool,
+) -> anyhow::Result<String> {
+    let (listed, _) = render::list(fs, root).await?;
+
+    let mut lines = Vec::new();
+    let mut destinations = BTreeMap::new();
+    let mut n = 0;
+
+    for entry in listed.iter().filter(|e| !e.is_dir) {
+        let Ok(relative) = entry.path.strip_prefix(root) else {
+            continue;
+        };
+        if !recursive && relative.components().count() > 1 {
+            continue;
+        }
+        let Some(name) = entry.path.file_name() else {
+            continue;
+        };
+
+        n += 1;
+        let modified = fs.stat(&entry.path).await?.and_then(|m| m.modified);
+        let context = TemplateContext {
+            path: &entry.path,
+            n,
+            modified,
+            captures: None,
+        };
+        let Some(folder) = organize.folder_for(relative, &context)? else {
+            continue;
+        };
+
+        let destination = root.join(folder).join(name);
+        if destination == entry.path {
+            continue;
+        }
+
+        if let Some(other) = destinations.insert(destination.clone(), entry.path.clone()) {
+            anyhow::bail!(
+                "both {} and {} would be moved to {}",
+                other.display(),
+                entry.path.display(),
+                destination.display()
+            );
+        }
+
+        lines.push(format!("{} : {}", entry.tag, entry.path.display()));
+        lines.push(format!("MOVE {} : {}", entry.tag, destination.display()));
+    }
+
+    format::format(&lines.join("\n"))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::{Path, PathBuf};
+
+    use crate::{fs::MemoryFileSystem, parse, plan};
+
+    use super::{Organize, organize_with};
+
+    fn moves(buffer: &str) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+        Ok(plan::plan(&parse(buffer)?)
+            .steps
+            .into_iter()
+            .map(|s| (s.source.unwrap_or_default(), s.path))
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn can_organize_by_extension() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/dl/a.PDF", "a")
+            .with_file("/dl/b.png", "b")
+            .with_file("/dl/pdf/c.pdf", "c")
+            .with_file("/dl/nested/d.png", "d")
+            .with_file("/dl/README", "e");
+
+        let buffer = organize_with(&fs, Path::new("/dl"), &Organize::by_extension(), false).await?;
+
+        pretty_assertions::assert_eq!(
+            vec![
+                ("/dl/a.PDF".into(), "/dl/pdf/a.PDF".into()),
+                ("/dl/b.png".into(), "/dl/png/b.png".into()),
+            ],
+            moves(&buffer)?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_organize_by_rules() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/dl/scan_1.PDF", "a")
+            .with_file("/dl/invoice.pdf", "b")
+            .with_file("/dl/holiday/beach.jpg", "c")
+            .with_file("/dl/notes.txt", "d");
+
+        let organize = Organize::from_toml(
+            r#"
+[[rule]]
+glob = "scan_*.pdf"
+folder = "scans"
+
+[[rule]]
+glob = "*.{pdf,jpg}"
+folder = "{ext}/{parent}"
+"#,
+        )?;
+        let buffer = organize_with(&fs, Path::new("/dl"), &organize, true).await?;
+
+        pretty_assertions::assert_eq!(
+            vec![
+                (
+                    "/dl/holiday/beach.jpg".into(),
+                    "/dl/jpg/holiday/beach.jpg".into()
+                ),
+                ("/dl/invoice.pdf".into(), "/dl/pdf/dl/invoice.pdf".into()),
+                ("/dl/scan_1.PDF".into(), "/dl/scans/scan_1.PDF".into()),
+            ],
+            moves(&buffer)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_invalid_rules() {
+        assert!(Organize::from_toml("").is_err());
+        assert!(Organize::from_toml("[[rule]]\nglob = \"[\"\nfolder = \"a\"").is_err());
+        assert!(Organize::from_toml("[[rule]]\nglob = \"*\"\nfolder = \"{nope:upper\"").is_err());
+    }
+}
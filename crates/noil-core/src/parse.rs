@@ -6,6 +6,10 @@ pub(crate) fn parse_input(input: &str) -> anyhow::Result<models::Buffer> {
     let mut files = Vec::default();
     // We are keeping parsing simple. For each line take any non empty lines, the first part should be an index. This is where the magic happens, if it contains special tokens handle accordingly, the path always comes after a :.
     for line in input.lines() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
         if let Some((left, right)) = line.trim().rsplit_once(" : ") {
             let path = PathBuf::from(right.trim());
             let file_entry = models::FileEntry::parse(left.trim())?;
@@ -19,3 +23,15 @@ pub(crate) fn parse_input(input: &str) -> anyhow::Result<models::Buffer> {
 
     Ok(models::Buffer { files })
 }
+
+/// The directory declared by a `# root: <path>` comment, the first one wins
+pub(crate) fn declared_root(input: &str) -> Option<PathBuf> {
+    input.lines().find_map(|line| {
+        let root = line
+            .trim()
+            .strip_prefix('#')?
+            .trim()
+            .strip_prefix("root:")?;
+        Some(PathBuf::from(root.trim()))
+    })
+}
@@ -0,0 +1,159 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::models::{Buffer, Operation};
+
+/// A buffer resolved into the steps apply will perform.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Plan {
+    /// Every operation in the buffer which touches the filesystem, in buffer order
+    pub steps: Vec<Step>,
+    /// The steps split into batches of independent steps, see [`plan`]. Each batch contains
+    /// indexes into `steps`.
+    pub batches: Vec<Vec<usize>>,
+}
+
+/// A single operation with its tag resolved to the path it refers to
+#[derive(Clone, PartialEq, Debug)]
+pub struct Step {
+    pub operation: Operation,
+    /// The path of the tagged entry for `COPY`, `MOVE`, `HARDLINK` and commands. `None` if the tag
+    /// doesn't exist in the buffer, or the operation doesn't take a source.
+    pub source: Option<PathBuf>,
+    /// The path written on the line of the operation
+    pub path: PathBuf,
+}
+
+impl Plan {
+    /// The steps one by one in buffer order, with no concurrency between them
+    pub fn sequential(&self) -> Vec<Vec<usize>> {
+        (0..self.steps.len()).map(|i| vec![i]).collect()
+    }
+}
+
+/// Resolves the operations of a buffer into steps, and splits them into batches, which can each
+/// be run concurrently.
+///
+/// A step depends on every earlier step that touches the same path, or a parent or child of it.
+/// It is placed in the batch after the last one of those, as such running the batches in order
+/// gives the same result as running the buffer line by line. `Existing` and `Open` entries are
+/// left out as they don't touch the filesystem.
+///
+/// Paths are compared as written in the buffer, `./a` and `a` are the same path, but a relative
+/// and an absolute path to the same file are not.
+pub fn plan(buffer: &Buffer) -> Plan {
+    let mut steps = Vec::new();
+    let mut batches: Vec<Vec<usize>> = Vec::new();
+    // (batch, touched paths) for each step
+    let mut scheduled: Vec<(usize, Vec<PathBuf>)> = Vec::new();
+
+    for file in &buffer.files {
+        let source = match &file.entry.operation {
+            Operation::Existing { .. } | Operation::Open { .. } => continue,
+            Operation::Add | Operation::Delete { .. } => None,
+            Operation::Copy { index } | Operation::Move { index } | Operation::Link { index } => {
+                buffer.get_existing(index).map(|e| e.path.clone())
+            }
+            Operation::Command { index, .. } => index
+                .as_ref()
+                .and_then(|index| buffer.get_existing(index))
+                .map(|e| e.path.clone()),
+        };
+
+        let step = Step {
+            operation: file.entry.operation.clone(),
+            source,
+            path: file.path.clone(),
+        };
+
+        let touched = step
+            .source
+            .iter()
+            .chain(std::iter::once(&step.path))
+            .map(|p| normalize(p))
+            .collect::<Vec<_>>();
+
+        let batch = scheduled
+            .iter()
+            .filter(|(_, other)| overlaps(&touched, other))
+            .map(|(batch, _)| batch + 1)
+            .max()
+            .unwrap_or_default();
+
+        if batches.len() <= batch {
+            batches.push(Vec::new());
+        }
+        batches[batch].push(steps.len());
+
+        tracing::trace!(batch, path = %step.path.display(), "planned step");
+        scheduled.push((batch, touched));
+        steps.push(step);
+    }
+
+    Plan { steps, batches }
+}
+
+fn overlaps(a: &[PathBuf], b: &[PathBuf]) -> bool {
+    a.iter()
+        .any(|a| b.iter().any(|b| a.starts_with(b) || b.starts_with(a)))
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{models::Operation, parse, plan::Step};
+
+    #[test]
+    fn independent_operations_share_a_batch() -> anyhow::Result<()> {
+        let input = r#"
+abc        : /var/a
+ecd        : /var/b
+COPY abc   : /tmp/a
+DELETE ecd : /var/b
+ADD        : /tmp/c
+"#;
+
+        let buffer = parse::parse_input(input)?;
+        let plan = super::plan(&buffer);
+
+        pretty_assertions::assert_eq!(vec![vec![0, 1, 2]], plan.batches);
+        pretty_assertions::assert_eq!(
+            Step {
+                operation: Operation::Copy {
+                    index: "abc".into()
+                },
+                source: Some("/var/a".into()),
+                path: "/tmp/a".into(),
+            },
+            plan.steps[0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependent_operations_keep_buffer_order() -> anyhow::Result<()> {
+        let input = r#"
+abc        : /var/a
+ecd        : /var/b
+ADD        : /var/a/new
+MOVE abc   : /tmp/a
+DELETE ecd : ./var/b
+ADD        : var/b/
+ADD        : /tmp/unrelated
+"#;
+
+        let buffer = parse::parse_input(input)?;
+
+        pretty_assertions::assert_eq!(
+            vec![vec![0, 2, 4], vec![1, 3]],
+            super::plan(&buffer).batches
+        );
+
+        Ok(())
+    }
+}
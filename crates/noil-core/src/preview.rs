@@ -0,0 +1,438 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use ansi_term::{Color, Style};
+use indicatif::HumanBytes;
+
+use crate::{
+    fs::{FileKind, FileSystem, WalkOptions, normalize},
+    models::{Buffer, Operation},
+    plan::{self, Step},
+};
+
+/// Renders the changes a buffer would make, one bullet per operation. Entries which don't change
+/// anything, such as existing files, are left out.
+pub fn preview(buffer: &Buffer, color: bool) -> String {
+    let mut output = String::new();
+
+    let paint = |style: Style, key: &str| {
+        if color {
+            style.paint(key).to_string()
+        } else {
+            key.to_string()
+        }
+    };
+
+    for item in &buffer.files {
+        let operation = &item.entry.operation;
+        let index = match operation {
+            Operation::Existing { .. } | Operation::Open { .. } => continue,
+            Operation::Add => None,
+            Operation::Copy { index }
+            | Operation::Delete { index }
+            | Operation::Move { index }
+            | Operation::Link { index } => Some(index.as_str()),
+            Operation::Command { index, .. } => index.as_deref(),
+        };
+
+        write_op(
+            &mut output,
+            &paint(style(operation), &operation.to_string()),
+            index,
+            Some(&item.path),
+        );
+    }
+
+    output
+}
+
+fn write_op(output: &mut String, key: &str, index: Option<&str>, path: Option<&Path>) {
+    // Writing to a string can't fail
+    let _ = match index {
+        Some(index) => match path {
+            Some(path) => writeln!(output, "  - {key} ({index}) - {}", path.display()),
+            None => writeln!(output, "  - {key} ({index})"),
+        },
+        None => match path {
+            Some(path) => writeln!(output, "  - {key} - {}", path.display()),
+            None => writeln!(output, "  - {key}"),
+        },
+    };
+}
+
+/// Same as [`preview`], but looks at the filesystem to describe every operation in detail.
+///
+/// Operations are grouped by the directory they write to. `COPY` and `MOVE` show where they copy
+/// or move from, `COPY`, `MOVE` and `DELETE` show how many files and bytes they touch, and
+/// destinations which already exist or sources which don't are warned about, taking the earlier
+/// operations into account. A summary of the operations, and the bytes copied and deleted, ends
+/// the preview.
+pub async fn preview_with(fs: &dyn FileSystem, buffer: &Buffer, color: bool) -> String {
+    let paint = |style: Style, key: &str| {
+        if color {
+            style.paint(key).to_string()
+        } else {
+            key.to_string()
+        }
+    };
+
+    let plan = plan::plan(buffer);
+    let mut groups: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut bytes = 0;
+    let mut changes = Changes::default();
+
+    let width = plan
+        .steps
+        .iter()
+        .map(|s| s.operation.to_string().len())
+        .max()
+        .unwrap_or_default();
+
+    for step in &plan.steps {
+        let name = step.operation.to_string();
+        let dir = match step.path.parent() {
+            // A bare relative path, such as `README.md`
+            Some(parent) if parent.as_os_str().is_empty() => PathBuf::from("."),
+            Some(parent) => parent.to_path_buf(),
+            None => PathBuf::from("/"),
+        };
+
+        let mut line = format!(
+            "  {}{}",
+            paint(style(&step.operation), &name),
+            " ".repeat(width - name.len() + 1)
+        );
+        match &step.source {
+            Some(source) => {
+                let _ = write!(
+                    line,
+                    "{} -> {}",
+                    relative(source, &dir),
+                    display_name(&step.path)
+                );
+            }
+            None => line.push_str(&display_name(&step.path)),
+        }
+
+        let sized = match &step.operation {
+            Operation::Delete { .. } => Some(&step.path),
+            Operation::Copy { .. } | Operation::Move { .. } => step.source.as_ref(),
+            _ => None,
+        };
+        let size = match sized {
+            Some(path) if changes.exists(fs, path).await => size_of(fs, path).await,
+            _ => None,
+        };
+        if let Some(size) = &size {
+            let _ = write!(line, " ({size})");
+            if !matches!(step.operation, Operation::Move { .. }) {
+                bytes += size.bytes;
+            }
+        }
+
+        for warning in warnings(fs, &changes, step).await {
+            let _ = write!(
+                line,
+                "  {}",
+                paint(Color::Yellow.normal(), &format!("! {warning}"))
+            );
+        }
+
+        changes.record(step);
+
+        match groups.iter_mut().find(|(d, _)| *d == dir) {
+            Some((_, lines)) => lines.push(line),
+            None => groups.push((dir, vec![line])),
+        }
+        match counts.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+
+    let mut output = String::new();
+    for (dir, lines) in &groups {
+        let _ = writeln!(output, "{}", paint(Style::new().bold(), &dir_name(dir)));
+        for line in lines {
+            let _ = writeln!(output, "{line}");
+        }
+    }
+
+    if !counts.is_empty() {
+        let summary = counts
+            .iter()
+            .map(|(name, n)| format!("{n} {}", plural(name, *n)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = match bytes {
+            0 => writeln!(output, "\n{summary}"),
+            bytes => writeln!(output, "\n{summary} ({})", HumanBytes(bytes)),
+        };
+    }
+
+    output
+}
+
+fn style(operation: &Operation) -> Style {
+    match operation {
+        Operation::Add => Color::Green.bold(),
+        Operation::Copy { .. } => Color::Blue.bold(),
+        Operation::Delete { .. } => Color::Red.bold(),
+        // Orange
+        Operation::Move { .. } => Color::RGB(224, 145, 64).bold(),
+        Operation::Link { .. } => Color::Cyan.bold(),
+        Operation::Command { .. } => Color::Purple.bold(),
+        Operation::Existing { .. } | Operation::Open { .. } => Style::new(),
+    }
+}
+
+struct Size {
+    /// `None` for a single file
+    files: Option<usize>,
+    bytes: u64,
+}
+
+impl std::fmt::Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.files {
+            Some(files) => write!(
+                f,
+                "{files} {}, {}",
+                if files == 1 { "file" } else { "files" },
+                HumanBytes(self.bytes)
+            ),
+            None => write!(f, "{}", HumanBytes(self.bytes)),
+        }
+    }
+}
+
+/// The files and bytes at and below `path`, `None` if it doesn't exist
+async fn size_of(fs: &dyn FileSystem, path: &Path) -> Option<Size> {
+    let metadata = fs.stat(path).await.ok()??;
+    if metadata.kind != FileKind::Dir {
+        return Some(Size {
+            files: None,
+            bytes: metadata.len,
+        });
+    }
+
+    let mut size = Size {
+        files: Some(0),
+        bytes: 0,
+    };
+    for entry in fs.walk(path, WalkOptions::default()).await.ok()? {
+        if entry.kind == FileKind::Dir {
+            continue;
+        }
+
+        size.files = size.files.map(|n| n + 1);
+        if let Ok(Some(metadata)) = fs.stat(&entry.path).await {
+            size.bytes += metadata.len;
+        }
+    }
+
+    Some(size)
+}
+
+/// The paths the operations before the current one created and removed, such that warnings match
+/// what the filesystem will look like by then
+#[derive(Default)]
+struct Changes {
+    /// In the order they were made, `true` if the path was created
+    paths: Vec<(PathBuf, bool)>,
+}
+
+impl Changes {
+    fn record(&mut self, step: &Step) {
+        match &step.operation {
+            Operation::Delete { .. } => self.paths.push((normalize(&step.path), false)),
+            Operation::Move { .. } => {
+                if let Some(source) = &step.source {
+                    self.paths.push((normalize(source), false));
+                }
+                self.paths.push((normalize(&step.path), true));
+            }
+            Operation::Add
+            | Operation::Copy { .. }
+            | Operation::Link { .. }
+            | Operation::Command { .. } => self.paths.push((normalize(&step.path), true)),
+            Operation::Existing { .. } | Operation::Open { .. } => {}
+        }
+    }
+
+    async fn exists(&self, fs: &dyn FileSystem, path: &Path) -> bool {
+        let normalized = normalize(path);
+        for (changed, created) in self.paths.iter().rev() {
+            if *changed == normalized {
+                return *created;
+            }
+            // Whatever was below a removed path is gone too
+            if !created && normalized.starts_with(changed) {
+                return false;
+            }
+        }
+
+        fs.exists(path).await
+    }
+}
+
+async fn warnings(fs: &dyn FileSystem, changes: &Changes, step: &Step) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let needs_source = matches!(
+        step.operation,
+        Operation::Copy { .. } | Operation::Move { .. } | Operation::Link { .. }
+    );
+    match &step.source {
+        Some(source) if needs_source && !changes.exists(fs, source).await => {
+            warnings.push(format!("{} doesn't exist", source.display()));
+        }
+        None if needs_source => warnings.push("tag isn't in the buffer".into()),
+        _ => {}
+    }
+
+    let writes = matches!(
+        step.operation,
+        Operation::Add
+            | Operation::Copy { .. }
+            | Operation::Move { .. }
+            | Operation::Command { .. }
+    );
+    let is_dir = step.path.to_string_lossy().ends_with('/');
+    if writes
+        && !(is_dir && matches!(step.operation, Operation::Add))
+        && changes.exists(fs, &step.path).await
+    {
+        warnings.push("already exists".into());
+    }
+    if matches!(step.operation, Operation::Delete { .. }) && !changes.exists(fs, &step.path).await {
+        warnings.push("doesn't exist".into());
+    }
+
+    warnings
+}
+
+/// `path` relative to `dir` if it is inside it, otherwise the full path
+fn relative(path: &Path, dir: &Path) -> String {
+    match path.strip_prefix(dir) {
+        Ok(relative) if !relative.as_os_str().is_empty() => {
+            let trailing = if path.to_string_lossy().ends_with('/') {
+                "/"
+            } else {
+                ""
+            };
+            format!("{}{trailing}", relative.display())
+        }
+        _ => path.display().to_string(),
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    match path.parent() {
+        Some(parent) => relative(path, parent),
+        None => path.display().to_string(),
+    }
+}
+
+fn dir_name(dir: &Path) -> String {
+    let dir = dir.display().to_string();
+    if dir.ends_with('/') {
+        dir
+    } else {
+        format!("{dir}/")
+    }
+}
+
+/// `MOVE` into `moves`, user defined operations are left as they are
+fn plural(name: &str, n: usize) -> String {
+    let word = match name {
+        "ADD" => "add",
+        "COPY" => "copy",
+        "DELETE" => "delete",
+        "MOVE" => "move",
+        "HARDLINK" => "hard link",
+        name => return name.to_string(),
+    };
+
+    match (word, n) {
+        (word, 1) => word.to_string(),
+        ("copy", _) => "copies".into(),
+        (word, _) => format!("{word}s"),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{fs::MemoryFileSystem, parse};
+
+    use super::preview_with;
+
+    #[tokio::test]
+    async fn can_preview_with_details() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/photos/a.jpg", "aaaa")
+            .with_file("/photos/old/b.jpg", "bb")
+            .with_file("/photos/old/c.jpg", "cc")
+            .with_file("/archive/a.jpg", "a");
+
+        let input = r#"
+abc          : /photos/a.jpg
+ecd          : /photos/old/
+COPY abc     : /photos/a-copy.jpg
+MOVE abc     : /archive/a.jpg
+DELETE ecd   : /photos/old/
+ADD          : /photos/new.txt
+"#;
+
+        pretty_assertions::assert_eq!(
+            r#"/photos/
+  COPY   a.jpg -> a-copy.jpg (4 B)
+  DELETE old/ (2 files, 4 B)
+  ADD    new.txt
+/archive/
+  MOVE   /photos/a.jpg -> a.jpg (4 B)  ! already exists
+
+1 copy, 1 move, 1 delete, 1 add (8 B)
+"#,
+            preview_with(&fs, &parse(input)?, false).await
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn warnings_follow_earlier_operations() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("a.txt", "aa")
+            .with_file("README.md", "r");
+
+        let input = r#"
+abc          : a.txt
+bcd          : b.txt
+efg          : README.md
+MOVE abc     : b.txt
+COPY bcd     : c.txt
+DELETE efg   : README.md
+ADD          : README.md
+DELETE abc   : a.txt
+"#;
+
+        pretty_assertions::assert_eq!(
+            r#"./
+  MOVE   a.txt -> b.txt (2 B)
+  COPY   b.txt -> c.txt
+  DELETE README.md (1 B)
+  ADD    README.md
+  DELETE a.txt  ! doesn't exist
+
+1 move, 1 copy, 2 deletes, 1 add (1 B)
+"#,
+            preview_with(&fs, &parse(input)?, false).await
+        );
+
+        Ok(())
+    }
+}
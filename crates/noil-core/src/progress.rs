@@ -0,0 +1,107 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::fs::{FileKind, FileSystem, WalkOptions};
+
+/// Progress display for apply, drawn on stderr.
+///
+/// Tracks bytes as the bar position, and files as the prefix, so both are visible at once. A
+/// disabled progress is a hidden bar, so callers don't have to care whether it is shown or not.
+pub struct Progress {
+    bar: ProgressBar,
+    files_done: AtomicU64,
+    files_total: u64,
+}
+
+impl Progress {
+    pub fn new(total: ProgressTotal, enabled: bool) -> Self {
+        let bar = if enabled {
+            ProgressBar::with_draw_target(Some(total.bytes), ProgressDrawTarget::stderr())
+        } else {
+            ProgressBar::hidden()
+        };
+
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{spinner} [{elapsed_precise}] [{bar:30}] {bytes}/{total_bytes} ({prefix} files, eta {eta}) {wide_msg}",
+            )
+            .expect("progress template to be valid")
+            .progress_chars("=> "),
+        );
+        bar.set_prefix(format!("0/{}", total.files));
+        if enabled {
+            bar.enable_steady_tick(Duration::from_millis(120));
+        }
+
+        Self {
+            bar,
+            files_done: AtomicU64::new(0),
+            files_total: total.files,
+        }
+    }
+
+    pub fn hidden() -> Self {
+        Self::new(ProgressTotal::default(), false)
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.bar.is_hidden()
+    }
+
+    pub fn set_operation(&self, operation: &str, path: &Path) {
+        self.bar
+            .set_message(format!("{operation} {}", path.display()));
+    }
+
+    pub fn inc_bytes(&self, bytes: u64) {
+        self.bar.inc(bytes);
+    }
+
+    pub fn inc_files(&self, files: u64) {
+        let done = self.files_done.fetch_add(files, Ordering::Relaxed) + files;
+        self.bar.set_prefix(format!("{done}/{}", self.files_total));
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct ProgressTotal {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl ProgressTotal {
+    /// Sums up the files and bytes below a path, a single file counts as one
+    pub async fn of_path(fs: &dyn FileSystem, path: &Path) -> Self {
+        let mut total = Self::default();
+
+        let Ok(entries) = fs.walk(path, WalkOptions::default()).await else {
+            return total;
+        };
+
+        for entry in entries {
+            total.files += 1;
+
+            if entry.kind == FileKind::File
+                && let Ok(Some(metadata)) = fs.stat(&entry.path).await
+            {
+                total.bytes += metadata.len;
+            }
+        }
+
+        total
+    }
+
+    pub fn add(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.files += other.files;
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use regex::Regex;
+
+use crate::{
+    format,
+    fs::FileSystem,
+    render,
+    template::{Template, TemplateContext},
+};
+
+/// Renames files whose name matches a pattern.
+///
+/// The matching part of the name is replaced by the replacement, which can refer to capture
+/// groups as `$1` or `${name}`, and is a [`crate::template`], such as `{n:03}-$1.{ext:lower}`.
+#[derive(Clone, Debug)]
+pub struct Rename {
+    pattern: Regex,
+    replacement: Template,
+}
+
+impl Rename {
+    pub fn new(pattern: &str, replacement: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(pattern).context("invalid pattern")?,
+            replacement: Template::parse(replacement).context("invalid replacement")?,
+        })
+    }
+
+    /// The new name of `path`, `None` if the name doesn't match
+    pub fn rename(
+        &self,
+        path: &Path,
+        n: usize,
+        modified: Option<std::time::SystemTime>,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+            return Ok(None);
+        };
+        let Some(captures) = self.pattern.captures(&name) else {
+            return Ok(None);
+        };
+
+        let replacement = self
+            .replacement
+            .render_escaped(
+                &TemplateContext {
+                    path,
+                    n,
+                    modified,
+                    captures: Some(&captures),
+                },
+                // Values of fields are literal, `$` only refers to groups in the replacement
+                |value| value.replace('$', "$$"),
+            )
+            .with_context(|| format!("rename {}", path.display()))?;
+
+        Ok(Some(
+            self.pattern
+                .replace(&name, replacement.as_str())
+                .to_string(),
+        ))
+    }
+}
+
+/// Lists the files below `root`, and generates a buffer moving every file matching `rename`.
+/// Files are numbered in path order, the buffer only lists the renamed files.
+pub async fn rename_with(
+    fs: &dyn FileSystem,
+    root: &Path,
+    rename: &Rename,
+) -> anyhow::Result<String> {
+    let (listed, _) = render::list(fs, root).await?;
+
+    let mut lines = Vec::new();
+    let mut destinations = BTreeMap::new();
+    let mut n = 0;
+
+    for entry in listed.iter().filter(|e| !e.is_dir) {
+        // The counter only counts matching files, as such the time is only looked up once the
+        // name matches
+        if !rename
+            .pattern
+            .is_match(&entry.path.file_name().unwrap_or_default().to_string_lossy())
+        {
+            continue;
+        }
+        n += 1;
+
+        let modified = fs.stat(&entry.path).await?.and_then(|m| m.modified);
+        let Some(name) = rename.rename(&entry.path, n, modified)? else {
+            continue;
+        };
+
+        let destination = entry.path.with_file_name(&name);
+        if destination == entry.path {
+            continue;
+        }
+
+        if let Some(other) = destinations.insert(destination.clone(), entry.path.clone()) {
+            anyhow::bail!(
+                "both {} and {} would be renamed to {}",
+                other.display(),
+                entry.path.display(),
+                destination.display()
+            );
+        }
+
+        lines.push(format!("{} : {}", entry.tag, entry.path.display()));
+        lines.push(format!("MOVE {} : {}", entry.tag, destination.display()));
+    }
+
+    format::format(&lines.join("\n"))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::{fs::MemoryFileSystem, parse, plan};
+
+    use super::{Rename, rename_with};
+
+    #[test]
+    fn can_rename_with_groups_and_fields() -> anyhow::Result<()> {
+        let rename = Rename::new(r"^IMG_(?<id>\d+)\.(?i)jpe?g$", "{n:02}-${id}.{ext:lower}")?;
+
+        pretty_assertions::assert_eq!(
+            Some("03-1234.jpeg".to_string()),
+            rename.rename(Path::new("/photos/IMG_1234.JPEG"), 3, None)?
+        );
+        pretty_assertions::assert_eq!(None, rename.rename(Path::new("/photos/a.png"), 1, None)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn can_generate_buffer() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/b.txt", "b")
+            .with_file("/src/c.md", "c");
+
+        let buffer =
+            rename_with(&fs, Path::new("/src"), &Rename::new(r"\.txt$", ".{n}.md")?).await?;
+        let plan = plan::plan(&parse(&buffer)?);
+
+        pretty_assertions::assert_eq!(
+            vec![
+                ("/src/a.txt".into(), "/src/a.1.md".into()),
+                ("/src/b.txt".into(), "/src/b.2.md".into()),
+            ],
+            plan.steps
+                .iter()
+                .map(|s| (s.source.clone().unwrap_or_default(), s.path.clone()))
+                .collect::<Vec<(std::path::PathBuf, std::path::PathBuf)>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_on_conflicting_names() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/b.txt", "b");
+
+        let res = rename_with(&fs, Path::new("/src"), &Rename::new(r".*", "same.txt")?).await;
+
+        assert!(res.is_err());
+
+        Ok(())
+    }
+}
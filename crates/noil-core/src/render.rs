@@ -0,0 +1,191 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    encode_rand, find_prefix,
+    fs::{FileKind, FileSystem, WalkEntry, WalkOptions},
+    report::{Report, ReportEntry},
+};
+
+/// An entry of a listing with its tag
+pub(crate) struct Listed {
+    pub(crate) tag: String,
+    /// The shortest prefix of the tag which is unique on its own
+    pub(crate) individual_tag: String,
+    pub(crate) path: PathBuf,
+    /// As walked, symlinks are not followed
+    pub(crate) kind: FileKind,
+    /// Whether the entry is, or links to, a directory
+    pub(crate) is_dir: bool,
+}
+
+/// Lists `path` and tags every entry, returns the entries in path order along with the length
+/// of the tags
+pub(crate) async fn list(fs: &dyn FileSystem, path: &Path) -> anyhow::Result<(Vec<Listed>, usize)> {
+    list_roots(fs, &[path]).await
+}
+
+/// Lists several roots as one listing, such that the tags are unique across all of them
+pub(crate) async fn list_roots(
+    fs: &dyn FileSystem,
+    roots: &[&Path],
+) -> anyhow::Result<(Vec<Listed>, usize)> {
+    let mut entries = Vec::new();
+    for root in roots {
+        entries.extend(
+            fs.walk(
+                root,
+                WalkOptions {
+                    respect_ignore: true,
+                    hash: true,
+                },
+            )
+            .await?,
+        );
+    }
+
+    if roots.len() > 1 {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries.dedup_by(|a, b| a.path == b.path);
+    }
+
+    Ok(tag(fs, entries).await)
+}
+
+/// Tags the given paths like [`list`] would, the paths are sorted and deduplicated. Paths which
+/// don't exist are listed as files.
+async fn list_paths(fs: &dyn FileSystem, paths: &[PathBuf]) -> (Vec<Listed>, usize) {
+    let mut paths = paths.to_vec();
+    paths.sort_unstable();
+    paths.dedup();
+
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        let kind = match fs.stat(&path).await {
+            Ok(Some(metadata)) => metadata.kind,
+            _ => FileKind::File,
+        };
+        entries.push(WalkEntry {
+            hash: None,
+            path,
+            kind,
+        });
+    }
+
+    tag(fs, entries).await
+}
+
+/// Tags the entries, using the hashes of the walk where there are any
+async fn tag(fs: &dyn FileSystem, entries: Vec<WalkEntry>) -> (Vec<Listed>, usize) {
+    let mut hashes = Vec::with_capacity(entries.len());
+    let mut paths = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let is_dir = match entry.kind {
+            FileKind::Dir => true,
+            FileKind::File => false,
+            FileKind::Symlink => fs.is_dir(&entry.path).await,
+        };
+
+        hashes.push(entry.hash.unwrap_or_else(|| hash_path(&entry.path)));
+        paths.push((entry.path, entry.kind, is_dir));
+    }
+
+    // The entries stay in path order, the walk is the only sort
+    let hashes = hashes.iter().map(String::as_str).collect::<Vec<&str>>();
+    let (shortest_len, global_prefixes, individual_prefixes) =
+        find_prefix::shortest_unique_prefixes(&hashes);
+
+    let listed = global_prefixes
+        .into_iter()
+        .zip(individual_prefixes)
+        .zip(paths)
+        .map(|((tag, individual_tag), (path, kind, is_dir))| Listed {
+            tag,
+            individual_tag,
+            path,
+            kind,
+            is_dir,
+        })
+        .collect();
+
+    (listed, shortest_len)
+}
+
+/// The hash of a path which its tag is a prefix of
+pub(crate) fn hash_path(path: &Path) -> String {
+    let hash = blake3::hash(path.to_string_lossy().as_bytes());
+    encode_rand::encode_256bit_base36(hash.as_bytes())
+}
+
+pub(crate) async fn render(
+    fs: &dyn FileSystem,
+    path: &Path,
+    no_color: bool,
+) -> anyhow::Result<String> {
+    let (listed, shortest_len) = list(fs, path).await?;
+
+    write_lines(&listed, shortest_len, no_color)
+}
+
+/// Renders the given paths as a buffer, see [`list_paths`]
+pub(crate) async fn render_paths(
+    fs: &dyn FileSystem,
+    paths: &[PathBuf],
+    no_color: bool,
+) -> anyhow::Result<String> {
+    let (listed, shortest_len) = list_paths(fs, paths).await;
+
+    write_lines(&listed, shortest_len, no_color)
+}
+
+fn write_lines(listed: &[Listed], shortest_len: usize, no_color: bool) -> anyhow::Result<String> {
+    let mut lines = Vec::with_capacity(listed.len());
+
+    for entry in listed {
+        let prefix = &entry.tag;
+        let individual_prefix = &entry.individual_tag;
+        let path_str = entry.path.display().to_string();
+        let mut line = String::new();
+        write!(
+            &mut line,
+            "   {}{}   :   {}{}",
+            {
+                if no_color {
+                    prefix.clone()
+                } else if let Some(suffix) = prefix.strip_prefix(individual_prefix.as_str()) {
+                    //format!("*{individual_prefix}*{suffix}")
+                    format!("{individual_prefix}{suffix}")
+                } else {
+                    prefix.clone()
+                }
+            },
+            " ".repeat(shortest_len - prefix.len()),
+            path_str,
+            {
+                if entry.is_dir && !path_str.trim_end().ends_with("/") {
+                    "/"
+                } else {
+                    ""
+                }
+            }
+        )?;
+
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Lists `path` as a report, see [`crate::report`]
+pub(crate) async fn report(fs: &dyn FileSystem, path: &Path) -> anyhow::Result<Report> {
+    let (listed, _) = list(fs, path).await?;
+
+    Ok(Report {
+        entries: listed
+            .iter()
+            .map(|entry| ReportEntry::listed(&entry.tag, &entry.path, entry.is_dir))
+            .collect(),
+    })
+}
@@ -0,0 +1,200 @@
+//! Machine readable descriptions of listings, plans and applied buffers.
+//!
+//! Every report is an object with a list of entries, each entry has the same keys, which are
+//! `null` when they don't apply:
+//!
+//! ```json
+//! {
+//!   "entries": [
+//!     {
+//!       "tag": "abc",
+//!       "path": "/tmp/nginx-copy",
+//!       "kind": "dir",
+//!       "operation": "COPY",
+//!       "source": "/etc/nginx/",
+//!       "destination": "/tmp/nginx-copy",
+//!       "status": "applied",
+//!       "error": null
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - `tag`: the tag of the listed entry, or the tag an operation refers to
+//! - `path`: the path written in the buffer
+//! - `kind`: `file` or `dir`, for operations a path ending in `/` is a directory
+//! - `operation`: `ADD`, `COPY`, `DELETE`, `MOVE`, `HARDLINK` or the name of a user defined
+//!   operation, `null` for listed entries
+//! - `source`: the path of the tagged entry for `COPY`, `MOVE` and `HARDLINK`
+//! - `destination`: the path created by `ADD`, `COPY`, `MOVE` and `HARDLINK`
+//! - `status`: see [`Status`]
+//! - `error`: why the operation failed, only set for `failed`
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    models::Operation,
+    plan::{Plan, Step},
+};
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub entries: Vec<ReportEntry>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReportEntry {
+    pub tag: Option<String>,
+    pub path: PathBuf,
+    pub kind: Option<EntryKind>,
+    pub operation: Option<String>,
+    pub source: Option<PathBuf>,
+    pub destination: Option<PathBuf>,
+    pub status: Status,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// An entry of a listing
+    Listed,
+    /// An operation which would be performed
+    Planned,
+    Applied,
+    /// The operation had nothing to do, such as adding a file which already exists
+    Skipped,
+    Failed,
+    /// The operation wasn't run, as an operation it depends on failed
+    NotRun,
+}
+
+impl Report {
+    /// The steps of a plan, before anything is applied
+    pub fn planned(plan: &Plan) -> Self {
+        Self {
+            entries: plan
+                .steps
+                .iter()
+                .map(|step| ReportEntry::step(step, Status::Planned, None))
+                .collect(),
+        }
+    }
+
+    /// Whether no operation failed
+    pub fn is_success(&self) -> bool {
+        self.entries.iter().all(|e| e.status != Status::Failed)
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl ReportEntry {
+    pub(crate) fn listed(tag: &str, path: &Path, is_dir: bool) -> Self {
+        Self {
+            tag: Some(tag.to_string()),
+            path: path.to_path_buf(),
+            kind: Some(if is_dir {
+                EntryKind::Dir
+            } else {
+                EntryKind::File
+            }),
+            operation: None,
+            source: None,
+            destination: None,
+            status: Status::Listed,
+            error: None,
+        }
+    }
+
+    pub(crate) fn step(step: &Step, status: Status, error: Option<String>) -> Self {
+        let (tag, destination) = match &step.operation {
+            Operation::Add => (None, Some(step.path.clone())),
+            Operation::Copy { index } | Operation::Move { index } | Operation::Link { index } => {
+                (Some(index.clone()), Some(step.path.clone()))
+            }
+            Operation::Delete { index } => (Some(index.clone()), None),
+            Operation::Command { index, .. } => (index.clone(), Some(step.path.clone())),
+            Operation::Existing { .. } | Operation::Open { .. } => (None, None),
+        };
+
+        let written = step.source.as_ref().unwrap_or(&step.path);
+        let kind = if written.to_string_lossy().ends_with('/') {
+            EntryKind::Dir
+        } else {
+            EntryKind::File
+        };
+
+        Self {
+            tag,
+            path: step.path.clone(),
+            kind: Some(kind),
+            operation: Some(step.operation.to_string()),
+            source: step.source.clone(),
+            destination,
+            status,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{parse, plan};
+
+    use super::Report;
+
+    #[test]
+    fn can_report_plan() -> anyhow::Result<()> {
+        let input = r#"
+abc        : /var/a/
+ecd        : /var/b
+COPY abc   : /tmp/a
+DELETE ecd : /var/b
+"#;
+
+        let buffer = parse::parse_input(input)?;
+        let report = Report::planned(&plan::plan(&buffer));
+
+        pretty_assertions::assert_eq!(
+            serde_json::json!({
+                "entries": [
+                    {
+                        "tag": "abc",
+                        "path": "/tmp/a",
+                        "kind": "dir",
+                        "operation": "COPY",
+                        "source": "/var/a/",
+                        "destination": "/tmp/a",
+                        "status": "planned",
+                        "error": null,
+                    },
+                    {
+                        "tag": "ecd",
+                        "path": "/var/b",
+                        "kind": "file",
+                        "operation": "DELETE",
+                        "source": null,
+                        "destination": null,
+                        "status": "planned",
+                        "error": null,
+                    }
+                ]
+            }),
+            serde_json::to_value(&report)?
+        );
+
+        Ok(())
+    }
+}
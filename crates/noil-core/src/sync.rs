@@ -0,0 +1,331 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    format,
+    fs::FileSystem,
+    render::{self, Listed},
+};
+
+/// Generates a buffer which makes `dst` look like `src`.
+///
+/// The trees are compared by their paths relative to the roots, and files by size and content
+/// hash. Files in `dst` which aren't in `src`, but have the same contents as a missing file, are
+/// moved in place instead of copied, such that renames in `src` are renames in `dst`.
+///
+/// The buffer is ordered such that applying it line by line works: first the files and
+/// directories in the way are deleted, then files are moved, copied and empty directories added,
+/// and last whatever is left in `dst` is deleted. Hidden and ignored files are left out on both
+/// sides.
+pub async fn sync_with(fs: &dyn FileSystem, src: &Path, dst: &Path) -> anyhow::Result<String> {
+    let (src_root, dst_root) = (fs.canonicalize(src).await?, fs.canonicalize(dst).await?);
+    if src_root.starts_with(&dst_root) || dst_root.starts_with(&src_root) {
+        anyhow::bail!(
+            "{} and {} can't contain each other",
+            src.display(),
+            dst.display()
+        );
+    }
+    if !fs.is_dir(src).await {
+        anyhow::bail!("{} is not a directory", src.display());
+    }
+
+    let roots = if fs.exists(dst).await {
+        vec![src, dst]
+    } else {
+        vec![src]
+    };
+    let (listed, _) = render::list_roots(fs, &roots).await?;
+
+    let mut src_entries = BTreeMap::new();
+    let mut dst_entries = BTreeMap::new();
+    for entry in &listed {
+        if let Ok(relative) = entry.path.strip_prefix(src) {
+            src_entries.insert(relative.to_path_buf(), entry);
+        } else if let Ok(relative) = entry.path.strip_prefix(dst) {
+            dst_entries.insert(relative.to_path_buf(), entry);
+        }
+    }
+    // The roots themselves are never changed
+    src_entries.remove(Path::new(""));
+    dst_entries.remove(Path::new(""));
+
+    let mut buffer = Buffer::default();
+
+    // Entries in dst in the way of a file or directory of another kind, and files which differ
+    let mut missing = Vec::new();
+    let mut in_the_way = Vec::new();
+    for (relative, s) in &src_entries {
+        match dst_entries.get(relative) {
+            None => missing.push(relative),
+            Some(d) if d.is_dir != s.is_dir => {
+                in_the_way.push(relative.clone());
+                buffer.delete(d);
+                missing.push(relative);
+            }
+            Some(d) if !s.is_dir && !same_contents(fs, s, d).await? => {
+                buffer.delete(d);
+                missing.push(relative);
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Files only in dst, which may be moved in place of missing files
+    let extra = dst_entries
+        .iter()
+        .filter(|(relative, d)| match src_entries.get(*relative) {
+            Some(s) => s.is_dir != d.is_dir,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    let mut movable: BTreeMap<(u64, [u8; 32]), Vec<&Listed>> = BTreeMap::new();
+    let missing_lens = file_lens(fs, missing.iter().map(|r| src_entries[*r])).await?;
+    for (relative, d) in &extra {
+        if d.is_dir || in_the_way.iter().any(|p| relative.starts_with(p)) {
+            continue;
+        }
+        let Some(len) = len(fs, d).await? else {
+            continue;
+        };
+        if missing_lens.contains(&len) {
+            movable
+                .entry((len, fs.hash_file(&d.path).await?))
+                .or_default()
+                .push(d);
+        }
+    }
+
+    let mut moved = BTreeSet::new();
+    for relative in &missing {
+        let s = src_entries[*relative];
+        let destination = dst.join(relative);
+
+        if s.is_dir {
+            let is_empty = !src_entries
+                .keys()
+                .any(|other| other != *relative && other.starts_with(relative));
+            if is_empty {
+                buffer.add_dir(&destination);
+            }
+            continue;
+        }
+
+        let candidate = match len(fs, s).await? {
+            Some(len) if !movable.is_empty() => {
+                let hash = fs.hash_file(&s.path).await?;
+                movable.get_mut(&(len, hash)).and_then(|c| c.pop())
+            }
+            _ => None,
+        };
+
+        match candidate {
+            Some(d) => {
+                moved.insert(d.path.clone());
+                buffer.moved(d, &destination);
+            }
+            None => buffer.copy(s, &destination),
+        }
+    }
+
+    // Whatever is left in dst, only the top most path of a deleted tree is deleted
+    let mut deleted: Vec<&Path> = Vec::new();
+    for (relative, d) in extra {
+        if moved.contains(&d.path)
+            || in_the_way.iter().any(|p| relative.starts_with(p))
+            || deleted.iter().any(|p| relative.starts_with(p))
+        {
+            continue;
+        }
+
+        deleted.push(relative);
+        buffer.delete_extra(d);
+    }
+
+    buffer.render()
+}
+
+async fn len(fs: &dyn FileSystem, entry: &Listed) -> anyhow::Result<Option<u64>> {
+    Ok(fs.stat(&entry.path).await?.map(|m| m.len))
+}
+
+async fn file_lens<'a>(
+    fs: &dyn FileSystem,
+    entries: impl Iterator<Item = &'a Listed>,
+) -> anyhow::Result<BTreeSet<u64>> {
+    let mut lens = BTreeSet::new();
+    for entry in entries.filter(|e| !e.is_dir) {
+        if let Some(len) = len(fs, entry).await? {
+            lens.insert(len);
+        }
+    }
+
+    Ok(lens)
+}
+
+async fn same_contents(fs: &dyn FileSystem, a: &Listed, b: &Listed) -> anyhow::Result<bool> {
+    if len(fs, a).await? != len(fs, b).await? {
+        return Ok(false);
+    }
+
+    Ok(fs.hash_file(&a.path).await? == fs.hash_file(&b.path).await?)
+}
+
+/// The lines of the buffer, grouped in the order they are applied
+#[derive(Default)]
+struct Buffer {
+    /// Sources of copies and moves, by tag
+    existing: BTreeMap<String, PathBuf>,
+    deletes: Vec<String>,
+    moves: Vec<String>,
+    copies: Vec<String>,
+    extra_deletes: Vec<String>,
+}
+
+impl Buffer {
+    fn delete(&mut self, entry: &Listed) {
+        self.deletes.push(delete_line(entry));
+    }
+
+    fn delete_extra(&mut self, entry: &Listed) {
+        self.extra_deletes.push(delete_line(entry));
+    }
+
+    fn moved(&mut self, entry: &Listed, destination: &Path) {
+        self.existing.insert(entry.tag.clone(), entry.path.clone());
+        self.moves
+            .push(format!("MOVE {} : {}", entry.tag, destination.display()));
+    }
+
+    fn copy(&mut self, entry: &Listed, destination: &Path) {
+        self.existing.insert(entry.tag.clone(), entry.path.clone());
+        self.copies
+            .push(format!("COPY {} : {}", entry.tag, destination.display()));
+    }
+
+    fn add_dir(&mut self, destination: &Path) {
+        self.copies
+            .push(format!("ADD : {}/", destination.display()));
+    }
+
+    fn render(self) -> anyhow::Result<String> {
+        let lines = self
+            .existing
+            .iter()
+            .map(|(tag, path)| format!("{tag} : {}", path.display()))
+            .chain(self.deletes)
+            .chain(self.moves)
+            .chain(self.copies)
+            .chain(self.extra_deletes)
+            .collect::<Vec<_>>();
+
+        format::format(&lines.join("\n"))
+    }
+}
+
+fn delete_line(entry: &Listed) -> String {
+    let path = entry.path.display().to_string();
+    if entry.is_dir && !path.ends_with('/') {
+        format!("DELETE {} : {path}/", entry.tag)
+    } else {
+        format!("DELETE {} : {path}", entry.tag)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use crate::{
+        apply::{ApplyOptions, apply_with},
+        fs::MemoryFileSystem,
+    };
+
+    use super::sync_with;
+
+    #[tokio::test]
+    async fn can_sync_trees() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/src/a.txt", "a")
+            .with_file("/src/b/c.txt", "c")
+            .with_file("/src/d.txt", "new")
+            .with_file("/src/e", "e")
+            .with_dir("/src/empty")
+            .with_file("/dst/a.txt", "a")
+            .with_file("/dst/old/c.txt", "c")
+            .with_file("/dst/old/stale.txt", "stale")
+            .with_file("/dst/d.txt", "old")
+            .with_file("/dst/e/f.txt", "f")
+            .with_file("/dst/extra.txt", "extra");
+
+        let buffer = sync_with(&fs, Path::new("/src"), Path::new("/dst")).await?;
+
+        let operations = buffer
+            .lines()
+            .filter_map(|l| {
+                let (left, path) = l.split_once(" : ")?;
+                let op = left.split_whitespace().next()?;
+                op.chars()
+                    .all(|c| c.is_uppercase())
+                    .then(|| format!("{op} {}", path.trim()))
+            })
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(
+            vec![
+                "DELETE /dst/d.txt",
+                "DELETE /dst/e/",
+                "MOVE /dst/b/c.txt",
+                "COPY /dst/d.txt",
+                "COPY /dst/e",
+                "ADD /dst/empty/",
+                "DELETE /dst/extra.txt",
+                "DELETE /dst/old/",
+            ],
+            operations
+        );
+
+        apply_with(
+            &fs,
+            &buffer,
+            ApplyOptions {
+                quiet: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let paths = fs.paths();
+        let src = paths
+            .iter()
+            .filter_map(|p| p.strip_prefix("/src"))
+            .collect::<Vec<_>>();
+        let dst = paths
+            .iter()
+            .filter_map(|p| p.strip_prefix("/dst"))
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(src, dst);
+        pretty_assertions::assert_eq!(Some(b"new".to_vec()), fs.read("/dst/d.txt"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fails_on_nested_trees() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new().with_file("/src/dst/a.txt", "a");
+
+        assert!(
+            sync_with(&fs, Path::new("/src"), Path::new("/src/dst"))
+                .await
+                .is_err()
+        );
+        assert!(
+            sync_with(&fs, Path::new("./src"), Path::new("src/dst"))
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+//! User defined operations and aliases.
+//!
+//! An alias is another name for an operation, such as `RM` for `DELETE`. A command is an
+//! operation which runs a shell command, such as `COMPRESS` running `gzip -c {src} > {dst}`:
+//!
+//! ```text
+//! abc        : /var/log/app.log
+//! COMPRESS abc : /backup/app.log.gz
+//! ```
+//!
+//! `{src}` is the path of the tagged entry and `{dst}` the path on the line of the operation,
+//! both quoted for the shell. The tag is optional, a command without one can't use `{src}`.
+//! Neither aliases nor commands can reuse the name of a built in operation.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+
+use crate::models::{
+    BUILTIN_OPERATIONS as BUILTIN, Buffer, FileEntry, Operation, is_operation_name,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Syntax {
+    /// The operation each alias stands for
+    aliases: BTreeMap<String, String>,
+    /// The command template of each user defined operation
+    commands: BTreeMap<String, String>,
+}
+
+impl Syntax {
+    pub fn new(
+        aliases: BTreeMap<String, String>,
+        commands: BTreeMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        for name in aliases.keys().chain(commands.keys()) {
+            if !is_operation_name(name) {
+                anyhow::bail!(
+                    "operation: {name} must be upper case letters, digits, - or _, and start with a letter"
+                );
+            }
+            if BUILTIN.contains(&name.as_str()) {
+                anyhow::bail!("operation: {name} is built in, and can't be redefined");
+            }
+        }
+
+        for (alias, target) in &aliases {
+            if aliases.contains_key(target) {
+                anyhow::bail!("alias: {alias} refers to another alias: {target}");
+            }
+            if !BUILTIN.contains(&target.as_str()) && !commands.contains_key(target) {
+                anyhow::bail!("alias: {alias} refers to an unknown operation: {target}");
+            }
+        }
+
+        for (name, command) in &commands {
+            if command.trim().is_empty() {
+                anyhow::bail!("operation: {name} has an empty command");
+            }
+        }
+
+        Ok(Self { aliases, commands })
+    }
+
+    /// Replaces aliases with the operations they stand for, and checks that every
+    /// [`Operation::Command`] refers to a known command. The operation as written is kept as the
+    /// raw operation.
+    pub fn resolve(&self, buffer: Buffer) -> anyhow::Result<Buffer> {
+        let mut files = Vec::with_capacity(buffer.files.len());
+
+        for mut file in buffer.files {
+            if let Operation::Command { name, index } = &file.entry.operation {
+                let target = self.aliases.get(name).unwrap_or(name);
+
+                let operation = if self.commands.contains_key(target) {
+                    Operation::Command {
+                        name: target.clone(),
+                        index: index.clone(),
+                    }
+                } else if BUILTIN.contains(&target.as_str()) {
+                    let entry = match index {
+                        Some(index) => format!("{target} {index}"),
+                        None => target.clone(),
+                    };
+                    FileEntry::parse(&entry)
+                        .with_context(|| format!("alias: {name}"))?
+                        .operation
+                } else {
+                    anyhow::bail!("operation: {name} is not supported");
+                };
+
+                file.entry = FileEntry {
+                    raw_op: file.entry.raw_op.or_else(|| Some(name.clone())),
+                    operation,
+                };
+            }
+
+            files.push(file);
+        }
+
+        Ok(Buffer { files })
+    }
+
+    /// The shell command to run for an operation
+    pub fn command(
+        &self,
+        name: &str,
+        source: Option<&Path>,
+        path: &Path,
+    ) -> anyhow::Result<String> {
+        let template = self
+            .commands
+            .get(name)
+            .with_context(|| format!("operation: {name} is not supported"))?;
+
+        let mut command = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            command.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(after) = rest.strip_prefix("{src}") {
+                let source = source.with_context(|| format!("{name} needs a tag for {{src}}"))?;
+                command.push_str(&quote(source));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("{dst}") {
+                command.push_str(&quote(path));
+                rest = after;
+            } else {
+                command.push('{');
+                rest = &rest[1..];
+            }
+        }
+        command.push_str(rest);
+
+        Ok(command)
+    }
+}
+
+/// Quotes a path for `sh`
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{collections::BTreeMap, path::Path};
+
+    use crate::{format, format_with, models::Operation, parse, parse_with};
+
+    use super::Syntax;
+
+    fn syntax() -> anyhow::Result<Syntax> {
+        Syntax::new(
+            BTreeMap::from([
+                ("RM".into(), "DELETE".into()),
+                ("GZ".into(), "COMPRESS".into()),
+            ]),
+            BTreeMap::from([("COMPRESS".into(), "gzip -c {src} > {dst}".into())]),
+        )
+    }
+
+    #[test]
+    fn can_resolve_aliases_and_commands() -> anyhow::Result<()> {
+        let input = r#"
+abc         : /var/a.log
+ecd         : /var/b.log
+GZ abc      : /tmp/it's.gz
+RM ecd      : /var/b.log
+"#;
+
+        let buffer = parse_with(input, &syntax()?)?;
+
+        pretty_assertions::assert_eq!(
+            vec![
+                Operation::Command {
+                    name: "COMPRESS".into(),
+                    index: Some("abc".into())
+                },
+                Operation::Delete {
+                    index: "ecd".into()
+                }
+            ],
+            buffer.files[2..]
+                .iter()
+                .map(|f| f.entry.operation.clone())
+                .collect::<Vec<_>>()
+        );
+        pretty_assertions::assert_eq!(Some("RM"), buffer.files[3].entry.raw_op.as_deref());
+        pretty_assertions::assert_eq!(
+            r#"gzip -c '/var/a.log' > '/tmp/it'\''s.gz'"#,
+            syntax()?.command(
+                "COMPRESS",
+                Some(Path::new("/var/a.log")),
+                Path::new("/tmp/it's.gz")
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_unknown_operations() -> anyhow::Result<()> {
+        assert!(parse("GZ a : /a").is_err());
+        assert!(parse("DELET abc : /x").is_err());
+        assert!(format("DELET abc : /x").is_err());
+        assert!(format_with("GZ a : /a", &syntax()?).is_ok());
+        assert!(
+            Syntax::new(
+                BTreeMap::from([("C".into(), "COPY".into())]),
+                BTreeMap::new()
+            )
+            .is_err()
+        );
+        assert!(
+            Syntax::new(
+                BTreeMap::from([("X".into(), "NOPE".into())]),
+                BTreeMap::new()
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+}
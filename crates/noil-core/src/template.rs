@@ -0,0 +1,364 @@
+//! Templates for generated paths, such as `{n:03}-{stem:kebab}.{ext}`.
+//!
+//! A field is written as `{key}` or `{key:spec}`, and `{{` and `}}` are literal braces:
+//!
+//! - `name`, `stem`, `ext`: the file name, the name without the extension, and the extension
+//! - `parent`: the name of the directory containing the file
+//! - `n`: a counter starting at 1, `{n:03}` pads it with zeroes to three digits
+//! - `mtime`: the modification time, `{mtime:%Y-%m}` formats it with strftime, the default is
+//!   `%Y-%m-%d`
+//! - `1`, `2`, ...: a capture group of the pattern, any other key is a named capture group
+//!
+//! Text fields take a case conversion as their spec: `upper`, `lower`, `title`, `snake`,
+//! `kebab` or `camel`. `${...}` is left as is, such that it can refer to a capture group in
+//! a regex replacement.
+
+use std::{path::Path, time::SystemTime};
+
+use anyhow::Context;
+use chrono::format::{Item, StrftimeItems};
+
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Field { key: String, spec: Option<String> },
+}
+
+/// What a template is rendered for
+pub struct TemplateContext<'a> {
+    pub path: &'a Path,
+    /// The value of `{n}`
+    pub n: usize,
+    pub modified: Option<SystemTime>,
+    pub captures: Option<&'a regex::Captures<'a>>,
+}
+
+impl Template {
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                // `${group}` is left as is, for regex replacements
+                '$' if chars.peek() == Some(&'{') => {
+                    literal.push('$');
+                    for c in chars.by_ref() {
+                        literal.push(c);
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => anyhow::bail!("unclosed {{ in template: {input}"),
+                        }
+                    }
+
+                    let (key, spec) = match field.split_once(':') {
+                        Some((key, spec)) => (key.trim(), Some(spec.to_string())),
+                        None => (field.trim(), None),
+                    };
+                    if key.is_empty() {
+                        anyhow::bail!("empty field in template: {input}");
+                    }
+                    validate(key, spec.as_deref())?;
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field {
+                        key: key.to_string(),
+                        spec,
+                    });
+                }
+                '}' => anyhow::bail!("unmatched }} in template: {input}, use }}}} for a brace"),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Renders the template, the values of fields are passed through `escape`, such that they
+    /// can't be mistaken for syntax of whatever the output is used in
+    pub fn render_escaped(
+        &self,
+        context: &TemplateContext,
+        escape: impl Fn(&str) -> String,
+    ) -> anyhow::Result<String> {
+        let mut output = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => output.push_str(literal),
+                Part::Field { key, spec } => {
+                    output.push_str(&escape(&field(context, key, spec.as_deref())?))
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> anyhow::Result<String> {
+        self.render_escaped(context, |value| value.to_string())
+    }
+}
+
+fn validate(key: &str, spec: Option<&str>) -> anyhow::Result<()> {
+    match (key, spec) {
+        (_, None) => Ok(()),
+        ("n", Some(spec)) => {
+            spec.parse::<usize>()
+                .with_context(|| format!("{{n:{spec}}} is not a width"))?;
+            Ok(())
+        }
+        ("mtime", Some(spec)) => {
+            if StrftimeItems::new(spec).any(|item| matches!(item, Item::Error)) {
+                anyhow::bail!("{{mtime:{spec}}} is not a valid time format");
+            }
+            Ok(())
+        }
+        (key, Some(spec)) => {
+            if Case::parse(spec).is_none() {
+                anyhow::bail!(
+                    "{{{key}:{spec}}}: unknown case, expected upper, lower, title, snake, kebab or camel"
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn field(context: &TemplateContext, key: &str, spec: Option<&str>) -> anyhow::Result<String> {
+    let path = context.path;
+    let name = || {
+        path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+
+    let value = match key {
+        "n" => {
+            return Ok(match spec {
+                Some(width) if width.starts_with('0') => {
+                    let width = width.parse::<usize>().unwrap_or_default();
+                    format!("{:0width$}", context.n)
+                }
+                Some(width) => {
+                    let width = width.parse::<usize>().unwrap_or_default();
+                    format!("{:width$}", context.n)
+                }
+                None => context.n.to_string(),
+            });
+        }
+        "mtime" => {
+            let modified = context
+                .modified
+                .with_context(|| format!("no modification time for {}", path.display()))?;
+            let time = chrono::DateTime::<chrono::Local>::from(modified);
+
+            return Ok(time.format(spec.unwrap_or(DEFAULT_TIME_FORMAT)).to_string());
+        }
+        "name" => name(),
+        "stem" => path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "ext" => path
+            .extension()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "parent" => path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        key => {
+            let captures = context
+                .captures
+                .with_context(|| format!("{{{key}}} is not a known field"))?;
+            let group = match key.parse::<usize>() {
+                Ok(index) => captures.get(index),
+                Err(_) => captures.name(key),
+            };
+
+            group
+                .with_context(|| format!("{{{key}}} is not a group of the pattern"))?
+                .as_str()
+                .to_string()
+        }
+    };
+
+    Ok(match spec.and_then(Case::parse) {
+        Some(case) => case.apply(&value),
+        None => value,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Kebab,
+    Camel,
+}
+
+impl Case {
+    fn parse(spec: &str) -> Option<Self> {
+        match spec {
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "title" => Some(Self::Title),
+            "snake" => Some(Self::Snake),
+            "kebab" => Some(Self::Kebab),
+            "camel" => Some(Self::Camel),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: &str) -> String {
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(|c| c.to_lowercase()))
+                    .collect(),
+                None => String::new(),
+            }
+        };
+
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Title => words(value)
+                .iter()
+                .map(|w| capitalize(w))
+                .collect::<Vec<String>>()
+                .join(" "),
+            Self::Snake => words(value).join("_").to_lowercase(),
+            Self::Kebab => words(value).join("-").to_lowercase(),
+            Self::Camel => words(value)
+                .iter()
+                .enumerate()
+                .map(|(i, w)| {
+                    if i == 0 {
+                        w.to_lowercase()
+                    } else {
+                        capitalize(w)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Splits on anything which isn't alphanumeric, and where a lowercase letter is followed by an
+/// uppercase one, such that `myFile name-2` is `my`, `File`, `name`, `2`
+fn words(value: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut prev_lower = false;
+
+    for (i, c) in value.char_indices() {
+        if !c.is_alphanumeric() {
+            if let Some(s) = start.take() {
+                words.push(&value[s..i]);
+            }
+            prev_lower = false;
+            continue;
+        }
+
+        if c.is_uppercase()
+            && prev_lower
+            && let Some(s) = start.replace(i)
+        {
+            words.push(&value[s..i]);
+        }
+        start.get_or_insert(i);
+        prev_lower = c.is_lowercase() || c.is_numeric();
+    }
+
+    if let Some(s) = start {
+        words.push(&value[s..]);
+    }
+
+    words
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use super::{Template, TemplateContext};
+
+    fn render(template: &str, path: &str, n: usize) -> anyhow::Result<String> {
+        Template::parse(template)?.render(&TemplateContext {
+            path: Path::new(path),
+            n,
+            modified: None,
+            captures: None,
+        })
+    }
+
+    #[test]
+    fn can_render_fields() -> anyhow::Result<()> {
+        pretty_assertions::assert_eq!(
+            "007-holiday.JPG",
+            render("{n:03}-{stem}.{ext:upper}", "/photos/holiday.jpg", 7)?
+        );
+        pretty_assertions::assert_eq!(
+            "photos/{my_file}",
+            render("{parent}/{{{stem:snake}}}", "/photos/myFile.txt", 1)?
+        );
+        pretty_assertions::assert_eq!(
+            "My File Name|my-file-name|myFileName",
+            render(
+                "{stem:title}|{stem:kebab}|{stem:camel}",
+                "/my_file NAME.txt",
+                1
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(Template::parse("{stem").is_err());
+        assert!(Template::parse("stem}").is_err());
+        assert!(Template::parse("{stem:shout}").is_err());
+        assert!(Template::parse("{n:x}").is_err());
+        assert!(render("{mtime}", "/a.txt", 1).is_err());
+        assert!(render("{1}", "/a.txt", 1).is_err());
+    }
+}
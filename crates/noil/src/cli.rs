@@ -2,12 +2,29 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use crate::cli::{apply::ApplyCommand, edit::EditCommand, fmt::FmtCommand, output::OutputCommand};
+use crate::{
+    cli::{
+        apply::ApplyCommand, dedupe::DedupeCommand, edit::EditCommand, fmt::FmtCommand,
+        from_paths::FromPathsCommand, organize::OrganizeCommand, output::OutputCommand,
+        rename::RenameCommand, sync::SyncCommand,
+    },
+    config::Config,
+};
 
 mod apply;
+mod dedupe;
 mod edit;
+mod editor;
 mod fmt;
+mod from_paths;
+mod git;
+mod organize;
 mod output;
+mod rename;
+mod session;
+mod sync;
+
+pub use output::OutputFormat;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -20,6 +37,10 @@ struct Command {
 
     #[arg(long = "no-color", default_value = "false")]
     no_color: bool,
+
+    /// Print the listing as a buffer, or as json
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -27,23 +48,37 @@ enum Commands {
     Edit(EditCommand),
     Apply(ApplyCommand),
     Fmt(FmtCommand),
+    FromPaths(FromPathsCommand),
+    Rename(RenameCommand),
+    Dedupe(DedupeCommand),
+    Sync(SyncCommand),
+    Organize(OrganizeCommand),
 }
 
 pub async fn execute() -> anyhow::Result<()> {
     let cli = Command::parse();
     tracing::debug!("Starting cli");
 
+    // Only loaded by the commands which use it, such that a broken config doesn't get in the way
+    // of the others
     match cli.command {
-        Some(Commands::Edit(cmd)) => cmd.execute().await,
-        Some(Commands::Fmt(cmd)) => cmd.execute().await,
-        Some(Commands::Apply(cmd)) => cmd.execute().await,
+        Some(Commands::Edit(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Fmt(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Apply(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::FromPaths(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Rename(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Dedupe(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Sync(cmd)) => cmd.execute(&Config::load().await?).await,
+        Some(Commands::Organize(cmd)) => cmd.execute(&Config::load().await?).await,
         None => {
             let path = match &cli.path {
                 Some(path) => path,
                 None => anyhow::bail!("a path is required if just using noil"),
             };
 
-            OutputCommand {}.execute(path, cli.no_color).await
+            OutputCommand {}
+                .execute(path, cli.no_color, cli.format, &Config::load().await?)
+                .await
         }
     }
 }
@@ -1,59 +1,189 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use tokio::io::AsyncReadExt;
 
+use anyhow::Context;
+use noil_core::{
+    apply::{ApplyOptions, apply_report, apply_with},
+    archive::{Archive, ArchiveFormat},
+    report::Status,
+};
+
 use crate::{
-    cli::edit::{ApplyOptions, apply},
-    commit::{Action, print_changes},
+    cli::{
+        OutputFormat,
+        session::{ApplyArgs, Session, Target, confine_root},
+    },
+    commit::print_changes,
+    config::Config,
 };
 
 #[derive(clap::Parser)]
 pub struct ApplyCommand {
-    #[arg(long = "commit")]
-    commit: bool,
+    /// Apply the buffer to the members of a tar or zip archive, as listed by `noil <archive>`
+    #[arg(long = "archive", conflicts_with = "git")]
+    archive: Option<PathBuf>,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+
+    /// Read the buffer as text, or as a json plan
+    #[arg(long = "input-format", value_enum, default_value_t = InputFormat::Text)]
+    input_format: InputFormat,
+
+    /// Print the plan, or with --commit the result of each operation, as json
+    #[arg(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
 
-    #[arg(long = "chooser-file", env = "NOIL_CHOOSER_FILE")]
-    chooser_file: Option<PathBuf>,
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum InputFormat {
+    /// A noil buffer
+    #[default]
+    Text,
+    /// A json plan, see the noil-core import module for the schema
+    Json,
 }
 
 impl ApplyCommand {
-    pub async fn execute(&self) -> anyhow::Result<()> {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
         let mut stdin = tokio::io::stdin();
         let mut buffer = Vec::new();
 
         stdin.read_to_end(&mut buffer).await?;
 
-        let input = String::from_utf8_lossy(&buffer);
-
-        if !self.commit {
-            let action = print_changes(&input, !self.commit).await?;
-            let res = match action {
-                Action::Quit => Ok(()),
-                Action::Apply { original } => {
-                    apply(
-                        &original,
-                        ApplyOptions {
-                            chooser_file: self.chooser_file.clone(),
-                            ..Default::default()
-                        },
-                    )
-                    .await
-                }
-                Action::Edit => todo!(),
-            };
+        let input = read_input(&String::from_utf8_lossy(&buffer), self.input_format, config)?;
 
-            eprintln!("\nin preview mode: add (--commit) to perform actions");
+        if self.apply.commit {
+            return self.apply(&input, config).await;
+        }
 
-            res
-        } else {
-            apply(
+        if self.format == OutputFormat::Json {
+            print_changes(
                 &input,
-                ApplyOptions {
-                    chooser_file: self.chooser_file.clone(),
-                    ..Default::default()
-                },
+                true,
+                self.format,
+                &config.syntax()?,
+                None,
+                false,
+                false,
             )
-            .await
+            .await?;
+            eprintln!("\nin preview mode: add (--commit) to perform actions");
+
+            return Ok(());
+        }
+
+        let target = match self.archive()? {
+            Some(archive) => Target::Archive(archive),
+            None => Target::Path(PathBuf::from(".")),
+        };
+
+        Session {
+            args: &self.apply,
+            config,
+            target,
+            confine: false,
+            declared_root: true,
         }
+        .run(&input, false)
+        .await
+    }
+
+    async fn apply(&self, input: &str, config: &Config) -> anyhow::Result<()> {
+        let options = self.apply.options(config)?;
+        let options = ApplyOptions {
+            quiet: options.quiet || self.format == OutputFormat::Json,
+            confine: (self.apply.confine(false) && self.archive.is_none())
+                .then(|| confine_root(input, Path::new("."))),
+            ..options
+        };
+
+        if self.format == OutputFormat::Json {
+            let report = match self.archive()? {
+                Some(archive) => archive.apply_report(input, options).await?,
+                None => {
+                    let fs = self.apply.git.filesystem(Path::new("."), config).await?;
+                    apply_report(fs.as_ref(), input, options).await?
+                }
+            };
+
+            println!("{}", report.to_json()?);
+
+            let failed = report
+                .entries
+                .iter()
+                .filter(|e| e.status == Status::Failed)
+                .count();
+            if failed > 0 {
+                anyhow::bail!("{failed} operations failed");
+            }
+
+            return Ok(());
+        }
+
+        match self.archive()? {
+            Some(archive) => archive.apply(input, options).await,
+            None => {
+                let fs = self.apply.git.filesystem(Path::new("."), config).await?;
+                apply_with(fs.as_ref(), input, options).await
+            }
+        }
+    }
+
+    fn archive(&self) -> anyhow::Result<Option<Archive>> {
+        let Some(path) = &self.archive else {
+            return Ok(None);
+        };
+
+        let format = ArchiveFormat::detect(path)
+            .with_context(|| format!("{} is not a tar or zip archive", path.display()))?;
+
+        Ok(Some(Archive {
+            path: path.clone(),
+            format,
+        }))
+    }
+}
+
+/// The buffer to apply, a json plan is turned into a buffer accepting the same operations
+fn read_input(input: &str, format: InputFormat, config: &Config) -> anyhow::Result<String> {
+    match format {
+        InputFormat::Text => Ok(input.to_string()),
+        InputFormat::Json => {
+            let buffer = noil_core::import::from_json(input)?;
+            noil_core::format_with(&buffer.to_string(), &config.syntax()?)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::config::Config;
+
+    use super::{InputFormat, read_input};
+
+    #[test]
+    fn json_accepts_configured_operations() -> anyhow::Result<()> {
+        let config: Config = toml::from_str("[operations]\nDUP = \"cp {src} {dst}\"")?;
+        let input = r#"
+{
+  "entries": [
+    { "tag": "abc", "path": "/a.txt" },
+    { "operation": "DUP", "tag": "abc", "path": "/b.txt" }
+  ]
+}
+"#;
+
+        let buffer = read_input(input, InputFormat::Json, &config)?;
+
+        assert!(
+            buffer
+                .lines()
+                .any(|l| l.starts_with("DUP") && l.ends_with("/b.txt"))
+        );
+        assert!(read_input(input, InputFormat::Json, &Config::default()).is_err());
+
+        Ok(())
     }
 }
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use noil_core::dedupe::DedupeMode;
+
+use crate::{
+    cli::session::{ApplyArgs, Session, Target},
+    config::Config,
+};
+
+/// Finds files with the same contents, reviewed as a buffer deleting all but one copy
+#[derive(Parser)]
+pub struct DedupeCommand {
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// Replace the duplicates with hard links instead of deleting them
+    #[arg(long = "hardlink")]
+    hardlink: bool,
+
+    /// Print the buffer instead of applying it
+    #[arg(long = "print")]
+    print: bool,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+impl DedupeCommand {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let fs = config.listing_filesystem()?;
+        let mode = if self.hardlink {
+            DedupeMode::Hardlink
+        } else {
+            DedupeMode::Delete
+        };
+        let buffer = noil_core::dedupe::dedupe_with(fs.as_ref(), &self.path, mode).await?;
+
+        if buffer.is_empty() {
+            eprintln!("no duplicates found");
+            return Ok(());
+        }
+
+        if self.print {
+            println!("{buffer}");
+            return Ok(());
+        }
+
+        Session {
+            args: &self.apply,
+            config,
+            target: Target::Path(self.path.clone()),
+            confine: false,
+            declared_root: false,
+        }
+        .run(&buffer, false)
+        .await
+    }
+}
@@ -0,0 +1,183 @@
+use std::{ffi::OsString, io::IsTerminal, path::Path, process::Stdio};
+
+use anyhow::Context;
+use tokio::fs::OpenOptions;
+
+use crate::config::Config;
+
+/// Used when neither `--editor`, `$NOIL_EDITOR`, the config, `$VISUAL` nor `$EDITOR` name one
+const FALLBACK: &str = "vi";
+
+/// The editor buffers are opened in, such as `code --wait`
+#[derive(Debug, PartialEq)]
+pub struct Editor {
+    program: String,
+    args: Vec<String>,
+}
+
+impl Editor {
+    /// Picks the first editor which is set of `--editor`, `$NOIL_EDITOR`, the config, `$VISUAL`
+    /// and `$EDITOR`, falling back to `vi`
+    pub fn resolve(flag: Option<&str>, config: &Config) -> anyhow::Result<Self> {
+        let command = choose(flag, config, |key| std::env::var(key).ok());
+        Self::parse(&command)
+    }
+
+    /// Splits the command like a shell would, such that arguments and quotes work
+    fn parse(command: &str) -> anyhow::Result<Self> {
+        let mut words = shell_words::split(command)
+            .with_context(|| format!("invalid editor command: {command}"))?
+            .into_iter();
+        let program = words
+            .next()
+            .with_context(|| format!("invalid editor command: {command}"))?;
+
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// Opens the file and waits for the editor to close. The cursor is placed on `line`, counted
+    /// from 1, if the editor is known to support it. The editor gets the terminal, even if stdin
+    /// or stdout aren't one, such as for `cat buffer.noil | noil apply`.
+    pub async fn open(&self, file_path: &Path, line: Option<usize>) -> anyhow::Result<()> {
+        let mut cmd = tokio::process::Command::new(&self.program);
+        cmd.args(&self.args).args(self.file_args(file_path, line));
+
+        // Graphical editors are fine without a terminal
+        if (!std::io::stdin().is_terminal() || !std::io::stdout().is_terminal())
+            && let Ok(tty) = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/tty")
+                .await
+        {
+            let tty_in = tty.try_clone().await.context("clone ttyin")?;
+            let tty_out = tty.try_clone().await.context("clone ttyout")?;
+
+            cmd.stdin(Stdio::from(tty_in.into_std().await))
+                .stdout(Stdio::from(tty_out.into_std().await))
+                .stderr(Stdio::from(tty.into_std().await));
+        }
+
+        let mut process = cmd.spawn().with_context(|| {
+            format!(
+                "failed to start editor: {}, choose one with --editor or $EDITOR",
+                self.program
+            )
+        })?;
+        let status = process.wait().await.context("editor closed prematurely")?;
+        if !status.success() {
+            let code = status.code().unwrap_or(-1);
+            anyhow::bail!("editor exited: {code}");
+        }
+
+        Ok(())
+    }
+
+    /// The arguments which open the file, with the cursor on `line` in the way the editor
+    /// understands
+    fn file_args(&self, file_path: &Path, line: Option<usize>) -> Vec<OsString> {
+        let Some(line) = line else {
+            return vec![file_path.into()];
+        };
+        let at_line = || {
+            let mut arg = OsString::from(file_path);
+            arg.push(format!(":{line}"));
+            arg
+        };
+
+        let name = Path::new(&self.program)
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match name.as_str() {
+            "vi" | "vim" | "nvim" | "gvim" | "view" | "nano" | "pico" | "emacs" | "emacsclient"
+            | "kak" | "micro" | "mg" | "joe" | "ne" | "gedit" => {
+                vec![format!("+{line}").into(), file_path.into()]
+            }
+            "hx" | "helix" | "subl" | "zed" | "zeditor" => vec![at_line()],
+            "code" | "code-insiders" | "codium" | "vscodium" | "cursor" => {
+                vec!["--goto".into(), at_line()]
+            }
+            "kate" => vec!["--line".into(), line.to_string().into(), file_path.into()],
+            _ => vec![file_path.into()],
+        }
+    }
+}
+
+fn choose(flag: Option<&str>, config: &Config, env: impl Fn(&str) -> Option<String>) -> String {
+    flag.map(str::to_string)
+        .into_iter()
+        .chain(env("NOIL_EDITOR"))
+        .chain(config.editor.clone())
+        .chain(env("VISUAL"))
+        .chain(env("EDITOR"))
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| FALLBACK.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{ffi::OsString, path::Path};
+
+    use crate::config::Config;
+
+    use super::{Editor, choose};
+
+    #[test]
+    fn can_choose_and_parse_editor() -> anyhow::Result<()> {
+        let config = Config {
+            editor: Some("hx".into()),
+            ..Default::default()
+        };
+        let env = |key: &str| match key {
+            "VISUAL" => Some(r#"code --wait --user-data-dir "/tmp/my dir""#.to_string()),
+            "EDITOR" => Some("nano".to_string()),
+            _ => None,
+        };
+
+        pretty_assertions::assert_eq!("nvim", choose(Some("nvim"), &config, env));
+        pretty_assertions::assert_eq!("hx", choose(None, &config, env));
+        pretty_assertions::assert_eq!(
+            Editor {
+                program: "code".into(),
+                args: vec![
+                    "--wait".into(),
+                    "--user-data-dir".into(),
+                    "/tmp/my dir".into()
+                ],
+            },
+            Editor::parse(&choose(None, &Config::default(), env))?
+        );
+        pretty_assertions::assert_eq!("vi", choose(None, &Config::default(), |_| None));
+        assert!(Editor::parse("code \"--wait").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn places_the_cursor_per_editor() -> anyhow::Result<()> {
+        let args = |command: &str, line| -> anyhow::Result<Vec<OsString>> {
+            Ok(Editor::parse(command)?.file_args(Path::new("/tmp/buf.noil"), line))
+        };
+
+        pretty_assertions::assert_eq!(
+            vec![OsString::from("+3"), "/tmp/buf.noil".into()],
+            args("/usr/bin/nvim", Some(3))?
+        );
+        pretty_assertions::assert_eq!(
+            vec![OsString::from("/tmp/buf.noil:3")],
+            args("hx", Some(3))?
+        );
+        pretty_assertions::assert_eq!(
+            vec![OsString::from("--goto"), "/tmp/buf.noil:3".into()],
+            args("code --wait", Some(3))?
+        );
+        pretty_assertions::assert_eq!(vec![OsString::from("/tmp/buf.noil")], args("ed", Some(3))?);
+        pretty_assertions::assert_eq!(vec![OsString::from("/tmp/buf.noil")], args("vim", None)?);
+
+        Ok(())
+    }
+}
@@ -1,13 +1,13 @@
 use clap::Parser;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use crate::format;
+use crate::config::Config;
 
 #[derive(Parser)]
 pub struct FmtCommand {}
 
 impl FmtCommand {
-    pub async fn execute(&self) -> anyhow::Result<()> {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
         let mut stdin = tokio::io::stdin();
         let mut buffer = Vec::new();
 
@@ -15,7 +15,7 @@ impl FmtCommand {
 
         let input = String::from_utf8_lossy(&buffer);
 
-        let output = format::format(&input)?;
+        let output = noil_core::format_with(&input, &config.syntax()?)?;
 
         let mut stdout = tokio::io::stdout();
         stdout.write_all(output.as_bytes()).await?;
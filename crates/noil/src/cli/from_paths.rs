@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::config::Config;
+
+/// Turns a list of paths on stdin into a buffer, such as the output of `fd` or `git ls-files`
+#[derive(Parser)]
+pub struct FromPathsCommand {
+    /// The paths are separated by NUL instead of newlines, such as from `fd -0`
+    #[arg(long = "null", short = '0')]
+    null: bool,
+
+    #[arg(long = "no-color", default_value = "false")]
+    no_color: bool,
+}
+
+impl FromPathsCommand {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let mut stdin = tokio::io::stdin();
+        let mut buffer = Vec::new();
+
+        stdin.read_to_end(&mut buffer).await?;
+
+        let input = String::from_utf8_lossy(&buffer);
+        let separator = if self.null { '\0' } else { '\n' };
+        let paths = input
+            .split(separator)
+            .map(|p| p.strip_suffix('\r').unwrap_or(p))
+            .filter(|p| !p.trim().is_empty())
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+
+        let output = noil_core::render_paths(&paths, !config.color(self.no_color)).await?;
+
+        let mut stdout = tokio::io::stdout();
+        stdout.write_all(output.as_bytes()).await?;
+        stdout.flush().await?;
+
+        Ok(())
+    }
+}
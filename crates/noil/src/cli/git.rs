@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use anyhow::Context;
+use noil_core::fs::{FileSystem, GitFileSystem, RealFileSystem};
+
+use crate::config::Config;
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct GitArgs {
+    /// Move and delete tracked files with `git mv` and `git rm`, keeping the index up to date
+    #[arg(long = "git")]
+    git: bool,
+
+    /// Stage files created by ADD and COPY, requires --git
+    #[arg(long = "git-add", requires = "git")]
+    git_add: bool,
+}
+
+impl GitArgs {
+    /// The filesystem to apply the buffer with, `path` is used to find the git work tree
+    pub async fn filesystem(
+        &self,
+        path: &Path,
+        config: &Config,
+    ) -> anyhow::Result<Box<dyn FileSystem>> {
+        if !self.git && !config.git.unwrap_or(false) {
+            return Ok(Box::new(RealFileSystem));
+        }
+
+        let git_add = self.git_add || config.git_add.unwrap_or(false);
+        let fs = GitFileSystem::discover(path, git_add)
+            .await
+            .context("--git")?;
+        tracing::debug!(root = %fs.root().display(), "using git work tree");
+
+        Ok(Box::new(fs))
+    }
+}
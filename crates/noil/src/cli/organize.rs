@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Parser;
+use noil_core::organize::Organize;
+
+use crate::{
+    cli::session::{ApplyArgs, Session, Target},
+    config::Config,
+};
+
+/// Sorts files into folders, reviewed as a buffer of moves
+#[derive(Parser)]
+pub struct OrganizeCommand {
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// `ext` for folders by extension, `mtime` for year/month folders by modification time, or
+    /// a rules file such as rules.toml
+    #[arg(long = "by", default_value = "ext")]
+    by: String,
+
+    /// Also sort the files in subdirectories
+    #[arg(long = "recursive", short = 'r')]
+    recursive: bool,
+
+    /// Print the buffer instead of applying it
+    #[arg(long = "print")]
+    print: bool,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+impl OrganizeCommand {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let fs = config.listing_filesystem()?;
+        let organize = match self.by.as_str() {
+            "ext" | "extension" => Organize::by_extension(),
+            "mtime" | "date" => Organize::by_modified(),
+            rules => {
+                let input = tokio::fs::read_to_string(rules)
+                    .await
+                    .with_context(|| format!("read rules file {rules}, or use --by ext|mtime"))?;
+                Organize::from_toml(&input).with_context(|| format!("rules file {rules}"))?
+            }
+        };
+
+        let buffer =
+            noil_core::organize::organize_with(fs.as_ref(), &self.path, &organize, self.recursive)
+                .await?;
+
+        if buffer.is_empty() {
+            eprintln!("nothing to organize");
+            return Ok(());
+        }
+
+        if self.print {
+            println!("{buffer}");
+            return Ok(());
+        }
+
+        Session {
+            args: &self.apply,
+            config,
+            target: Target::Path(self.path.clone()),
+            confine: false,
+            declared_root: false,
+        }
+        .run(&buffer, false)
+        .await
+    }
+}
@@ -1,16 +1,45 @@
 use std::path::Path;
 
 use clap::Parser;
+use noil_core::archive::Archive;
 use tokio::io::AsyncWriteExt;
 
-use crate::output::get_outputs;
+use crate::config::Config;
+
+/// How listings, plans and results are printed
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A noil buffer, or a readable list of changes
+    #[default]
+    Text,
+    /// Json, see the noil-core report module for the schema
+    Json,
+}
 
 #[derive(Parser)]
 pub struct OutputCommand {}
 
 impl OutputCommand {
-    pub async fn execute(&self, path: &Path, no_color: bool) -> anyhow::Result<()> {
-        let output = get_outputs(path, no_color).await?;
+    pub async fn execute(
+        &self,
+        path: &Path,
+        no_color: bool,
+        format: OutputFormat,
+        config: &Config,
+    ) -> anyhow::Result<()> {
+        let no_color = !config.color(no_color);
+        let fs = config.listing_filesystem()?;
+        let archive = Archive::detect(path);
+        let output = match (format, archive) {
+            (OutputFormat::Text, Some(archive)) => archive.render(no_color).await?,
+            (OutputFormat::Text, None) => {
+                noil_core::render_with(fs.as_ref(), path, no_color).await?
+            }
+            (OutputFormat::Json, Some(archive)) => archive.list().await?.to_json()? + "\n",
+            (OutputFormat::Json, None) => {
+                noil_core::list_with(fs.as_ref(), path).await?.to_json()? + "\n"
+            }
+        };
 
         let mut stdout = tokio::io::stdout();
         stdout.write_all(output.as_bytes()).await?;
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use noil_core::rename::Rename;
+
+use crate::{
+    cli::session::{ApplyArgs, Session, Target},
+    config::Config,
+};
+
+/// Renames files whose name matches a regex, reviewed as a buffer of MOVE operations
+#[derive(Parser)]
+pub struct RenameCommand {
+    /// A regex matched against the file names
+    pattern: String,
+
+    /// Replaces the matching part of the name. Refers to groups as $1 or ${name}, and takes
+    /// fields such as {stem}, {ext}, {n:03}, {mtime:%Y-%m-%d} and case conversions such as
+    /// {stem:kebab}
+    replacement: String,
+
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// Print the buffer instead of applying it
+    #[arg(long = "print")]
+    print: bool,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+impl RenameCommand {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let fs = config.listing_filesystem()?;
+        let rename = Rename::new(&self.pattern, &self.replacement)?;
+        let buffer = noil_core::rename::rename_with(fs.as_ref(), &self.path, &rename).await?;
+
+        if buffer.is_empty() {
+            eprintln!("no files to rename");
+            return Ok(());
+        }
+
+        if self.print {
+            println!("{buffer}");
+            return Ok(());
+        }
+
+        Session {
+            args: &self.apply,
+            config,
+            target: Target::Path(self.path.clone()),
+            confine: false,
+            declared_root: false,
+        }
+        .run(&buffer, false)
+        .await
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    env::temp_dir,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use ansi_term::Color;
+use anyhow::Context;
+use noil_core::{
+    apply::{ApplyOptions, Conflict, apply_with},
+    archive::Archive,
+    fs::{FileSystem, RealFileSystem},
+};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    cli::{OutputFormat, editor::Editor, git::GitArgs},
+    commit::{Action, print_changes, read_answer},
+    config::Config,
+};
+
+const ID_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+const PREVIEW: bool = false;
+
+const BUFFER_FILE: &str = "buf.noil";
+
+/// Next to the buffer, records what it is applied to, such that it can be resumed
+const TARGET_FILE: &str = "target";
+
+/// The first line of every buffer, such that editors which don't know `.noil` files still
+/// highlight them as noil. It is a comment to noil itself.
+const MODELINE: &str = "# -*- mode: noil -*- vim: set filetype=noil:";
+
+/// Options for commands which apply a buffer once it has been reviewed
+#[derive(clap::Args)]
+pub struct ApplyArgs {
+    #[arg(long = "chooser-file", env = "NOIL_CHOOSER_FILE")]
+    pub chooser_file: Option<PathBuf>,
+
+    /// Apply without asking for confirmation
+    #[arg(long = "commit", visible_alias = "yes", short = 'y')]
+    pub commit: bool,
+
+    /// Confirm every operation on its own, skipped operations are commented out of the buffer
+    #[arg(long = "interactive", short = 'i', conflicts_with = "commit")]
+    pub interactive: bool,
+
+    #[arg(long = "quiet")]
+    pub quiet: bool,
+
+    #[arg(long = "no-color")]
+    pub no_color: bool,
+
+    /// The command to edit the buffer with, in place of `$NOIL_EDITOR`, the config, `$VISUAL`
+    /// and `$EDITOR`
+    #[arg(long = "editor")]
+    pub editor: Option<String>,
+
+    /// Run up to N independent operations concurrently [default: 1]
+    #[arg(long = "jobs", short = 'j')]
+    pub jobs: Option<usize>,
+
+    /// What to do when a destination already exists: keep (the default) skips ADD and COPY
+    /// and fails MOVE, skip, fail or overwrite
+    #[arg(long = "conflict")]
+    pub conflict: Option<Conflict>,
+
+    /// Move deleted and overwritten paths to the trash instead of removing them
+    #[arg(long = "trash", overrides_with = "no_trash")]
+    pub trash: bool,
+
+    /// Remove deleted paths, even if the config enables the trash
+    #[arg(long = "no-trash")]
+    pub no_trash: bool,
+
+    /// Don't run the hooks from the config
+    #[arg(long = "no-hooks")]
+    pub no_hooks: bool,
+
+    /// Delete more files than the `max_deletes` of the config, 1000 by default
+    #[arg(long = "force")]
+    pub force: bool,
+
+    /// Refuse operations outside the listed directory, on by default for `noil edit`
+    #[arg(long = "confine", overrides_with = "no_confine")]
+    pub confine: bool,
+
+    /// Allow operations outside the listed directory
+    #[arg(long = "no-confine")]
+    pub no_confine: bool,
+
+    #[command(flatten)]
+    pub git: GitArgs,
+}
+
+impl ApplyArgs {
+    /// The options given on the command line, falling back to the config
+    pub fn options(&self, config: &Config) -> anyhow::Result<ApplyOptions> {
+        let quiet = self.quiet || config.quiet.unwrap_or(false);
+        let trash = match (self.trash, self.no_trash) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => config.trash.unwrap_or(false),
+        };
+
+        Ok(ApplyOptions {
+            chooser_file: self.chooser_file.clone(),
+            quiet,
+            progress: !quiet && std::io::stderr().is_terminal(),
+            jobs: self.jobs.or(config.jobs).unwrap_or(1),
+            conflict: self.conflict.or(config.conflict).unwrap_or_default(),
+            syntax: config.syntax()?,
+            guard: config.guard(self.force)?,
+            confine: None,
+            hooks: if self.no_hooks {
+                Default::default()
+            } else {
+                config.hooks.clone()
+            },
+            trash: if trash {
+                Some(config.trash_run_dir()?)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Whether operations are confined, given the default of the command
+    pub fn confine(&self, default: bool) -> bool {
+        match (self.confine, self.no_confine) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => default,
+        }
+    }
+}
+
+/// What a buffer is applied to
+pub enum Target {
+    /// The filesystem, the path is used to find the git work tree for --git
+    Path(PathBuf),
+    Archive(Archive),
+}
+
+impl Target {
+    /// A line such as `path /home/me/src`, the path is made absolute
+    fn describe(&self) -> anyhow::Result<String> {
+        let (kind, path) = match self {
+            Target::Path(path) => ("path", path),
+            Target::Archive(archive) => ("archive", &archive.path),
+        };
+        let path = std::path::absolute(path).context("absolute path of target")?;
+
+        Ok(format!("{kind} {}\n", path.display()))
+    }
+
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        let line = line.trim_end_matches('\n');
+        match line.split_once(' ') {
+            Some(("path", path)) => Ok(Target::Path(PathBuf::from(path))),
+            Some(("archive", path)) => Archive::detect(Path::new(path))
+                .map(Target::Archive)
+                .with_context(|| format!("{path} is no longer an archive")),
+            _ => anyhow::bail!("invalid target of buffer: {line}"),
+        }
+    }
+}
+
+/// Reviews a buffer, by editing it in `$EDITOR` and asking for confirmation, and applies it
+pub struct Session<'a> {
+    pub args: &'a ApplyArgs,
+    pub config: &'a Config,
+    pub target: Target,
+    /// Whether operations are confined to the target path, unless `--confine` or `--no-confine`
+    /// says otherwise
+    pub confine: bool,
+    /// Whether a `# root:` line in the buffer replaces the target path as the root to confine
+    /// to. Only for stand-alone buffers, an edited directory is always the root.
+    pub declared_root: bool,
+}
+
+impl Session<'_> {
+    /// Starts in the editor if `edit` is set, otherwise with the confirmation of the buffer as is
+    pub async fn run(&self, buffer: &str, edit: bool) -> anyhow::Result<()> {
+        let file_path = create_buffer_file(buffer, &self.target).await?;
+        self.review(&file_path, edit, None).await
+    }
+
+    /// Starts in the editor with the cursor on `line` of the buffer, counted from 1
+    pub async fn run_at(&self, buffer: &str, line: Option<usize>) -> anyhow::Result<()> {
+        let file_path = create_buffer_file(buffer, &self.target).await?;
+        // The modeline comes first
+        self.review(&file_path, true, line.map(|line| line + 1))
+            .await
+    }
+
+    /// Reopens a buffer which wasn't applied in the editor, see [`last_unfinished`]
+    pub async fn resume(&self, file_path: &Path) -> anyhow::Result<()> {
+        eprintln!("resuming {}", file_path.display());
+        self.review(file_path, true, None).await
+    }
+
+    /// Removes the buffer once it has been applied, and points to it if anything fails, such
+    /// that the edits aren't lost
+    async fn review(
+        &self,
+        file_path: &Path,
+        edit: bool,
+        line: Option<usize>,
+    ) -> anyhow::Result<()> {
+        match self.review_loop(file_path, edit, line).await {
+            Ok(true) => {
+                if let Some(dir) = file_path.parent()
+                    && let Err(e) = tokio::fs::remove_dir_all(dir).await
+                {
+                    tracing::warn!("failed to clean up {}: {e}", dir.display());
+                }
+                Ok(())
+            }
+            Ok(false) => Ok(()),
+            Err(e) => {
+                eprintln!(
+                    "the buffer is kept in {}, continue with: noil edit --resume",
+                    file_path.display()
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns whether the whole buffer was applied. The cursor is only placed on `line` the
+    /// first time the editor opens.
+    async fn review_loop(
+        &self,
+        file_path: &Path,
+        mut edit: bool,
+        mut line: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let syntax = self.config.syntax()?;
+        let editor = Editor::resolve(self.args.editor.as_deref(), self.config)?;
+
+        loop {
+            if edit {
+                editor.open(file_path, line.take()).await?;
+            }
+            edit = true;
+
+            let noil_content = tokio::fs::read_to_string(file_path)
+                .await
+                .context("read noil file")?;
+
+            let action = if !self.args.commit {
+                if let Err(e) = noil_core::parse_with(&noil_content, &syntax) {
+                    eprintln!(
+                        "Invalid operation\n{}\n\nreverting to edit on any key press: ",
+                        Color::Red.normal().paint(format!("{e:?}"))
+                    );
+
+                    read_answer().await.context("user finished prematurely")?;
+
+                    continue;
+                }
+
+                let fs = match &self.target {
+                    Target::Path(_) => Some(&RealFileSystem as &dyn FileSystem),
+                    Target::Archive(_) => None,
+                };
+                print_changes(
+                    &noil_content,
+                    PREVIEW,
+                    OutputFormat::Text,
+                    &syntax,
+                    fs,
+                    self.config.color(self.args.no_color),
+                    self.args.interactive,
+                )
+                .await?
+            } else {
+                Action::Apply {
+                    original: noil_content.clone(),
+                    skipped: None,
+                }
+            };
+
+            match action {
+                Action::Quit => return Ok(false),
+                Action::Apply { original, skipped } => {
+                    self.apply(&original).await?;
+
+                    let Some(skipped) = skipped else {
+                        return Ok(true);
+                    };
+                    write_buffer_file(file_path, &skipped).await?;
+                    eprintln!(
+                        "applied operations are commented out in {}, continue with the skipped ones with: noil edit --resume",
+                        file_path.display()
+                    );
+                    return Ok(false);
+                }
+                Action::Edit { buffer } => {
+                    if let Some(buffer) = buffer {
+                        write_buffer_file(file_path, &buffer).await?;
+                    }
+                    continue;
+                }
+            }
+        }
+    }
+
+    async fn apply(&self, input: &str) -> anyhow::Result<()> {
+        let options = self.args.options(self.config)?;
+
+        match &self.target {
+            Target::Archive(archive) => archive.apply(input, options).await,
+            Target::Path(path) => {
+                let options = ApplyOptions {
+                    confine: self.args.confine(self.confine).then(|| {
+                        if self.declared_root {
+                            confine_root(input, path)
+                        } else {
+                            path.clone()
+                        }
+                    }),
+                    ..options
+                };
+                let fs = self.args.git.filesystem(path, self.config).await?;
+                apply_with(fs.as_ref(), input, options).await
+            }
+        }
+    }
+}
+
+/// The directory operations are confined to, the root the buffer declares, or `path`
+pub fn confine_root(input: &str, path: &Path) -> PathBuf {
+    noil_core::declared_root(input).unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Writes the buffer to a new file in the temp dir, along with what it is applied to
+async fn create_buffer_file(buffer: &str, target: &Target) -> anyhow::Result<PathBuf> {
+    let mut small_id = Vec::new();
+
+    for _ in 0..8 {
+        small_id.push(ID_ALPHABET[rand::random_range(0..ID_ALPHABET.len())]);
+    }
+    let small_id = String::from_utf8_lossy(&small_id);
+
+    let file_path = temp_dir()
+        .join("noil")
+        .join(small_id.to_string())
+        .join(BUFFER_FILE);
+
+    if let Some(parent) = file_path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .context("failed to create temp dir file")?;
+    }
+
+    write_buffer_file(&file_path, &format!("{MODELINE}\n{buffer}")).await?;
+    tokio::fs::write(file_path.with_file_name(TARGET_FILE), target.describe()?)
+        .await
+        .context("write target of buffer")?;
+
+    Ok(file_path)
+}
+
+/// The most recent buffer which wasn't applied, and what it is applied to
+pub async fn last_unfinished() -> anyhow::Result<(PathBuf, Target)> {
+    let mut dirs = match tokio::fs::read_dir(temp_dir().join("noil")).await {
+        Ok(dirs) => dirs,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("no unfinished buffer to resume")
+        }
+        Err(e) => return Err(e).context("list buffers"),
+    };
+
+    let mut latest = None;
+    while let Some(dir) = dirs.next_entry().await? {
+        let file_path = dir.path().join(BUFFER_FILE);
+        // Buffers from before targets were written can't be resumed
+        if !file_path.with_file_name(TARGET_FILE).exists() {
+            continue;
+        }
+        let Ok(modified) = tokio::fs::metadata(&file_path)
+            .await
+            .and_then(|m| m.modified())
+        else {
+            continue;
+        };
+
+        if latest.as_ref().is_none_or(|(m, _)| modified > *m) {
+            latest = Some((modified, file_path));
+        }
+    }
+
+    let (_, file_path) = latest.context("no unfinished buffer to resume")?;
+    let target = tokio::fs::read_to_string(file_path.with_file_name(TARGET_FILE))
+        .await
+        .context("read target of buffer")?;
+
+    Ok((file_path.clone(), Target::parse(&target)?))
+}
+
+async fn write_buffer_file(file_path: &Path, buffer: &str) -> anyhow::Result<()> {
+    let mut file = tokio::fs::File::create(file_path)
+        .await
+        .context("create temp file for noil")?;
+
+    file.write_all(buffer.as_bytes())
+        .await
+        .context("write contents for edit")?;
+    file.flush().await.context("flush contents for edit")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::MODELINE;
+
+    #[test]
+    fn fmt_keeps_the_mode_line() -> anyhow::Result<()> {
+        let buffer = format!("{MODELINE}\nabc : /a\nMOVE abc : /b");
+
+        let formatted = noil_core::format(&buffer)?;
+
+        pretty_assertions::assert_eq!(
+            vec![MODELINE, "       abc   :   /a", "MOVE   abc   :   /b"],
+            formatted.lines().collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
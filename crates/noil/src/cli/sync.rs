@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::{
+    cli::session::{ApplyArgs, Session, Target},
+    config::Config,
+};
+
+/// Makes the destination look like the source, reviewed as a buffer of copies, moves and deletes
+#[derive(Parser)]
+pub struct SyncCommand {
+    src: PathBuf,
+
+    dst: PathBuf,
+
+    /// Print the buffer instead of applying it
+    #[arg(long = "print")]
+    print: bool,
+
+    #[command(flatten)]
+    apply: ApplyArgs,
+}
+
+impl SyncCommand {
+    pub async fn execute(&self, config: &Config) -> anyhow::Result<()> {
+        let fs = config.listing_filesystem()?;
+        let buffer = noil_core::sync::sync_with(fs.as_ref(), &self.src, &self.dst).await?;
+
+        if buffer.is_empty() {
+            eprintln!("already in sync");
+            return Ok(());
+        }
+
+        if self.print {
+            println!("{buffer}");
+            return Ok(());
+        }
+
+        Session {
+            args: &self.apply,
+            config,
+            target: Target::Path(self.dst.clone()),
+            confine: false,
+            declared_root: false,
+        }
+        .run(&buffer, false)
+        .await
+    }
+}
@@ -1,84 +1,67 @@
-use std::path::Path;
-
 use ansi_term::Color;
 use anyhow::Context;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use noil_core::{Operation, fs::FileSystem, report::Report, syntax::Syntax};
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-use std::io::Write;
+use crate::cli::OutputFormat;
 
-use crate::{models::Operation, parse::parse_input};
+use std::io::{IsTerminal, Write};
 
 pub enum Action {
     Quit,
-    Apply { original: String },
-    Edit,
+    Apply {
+        original: String,
+        /// The buffer to keep when operations were skipped, in which the applied operations are
+        /// commented out instead, such that resuming only runs the skipped ones
+        skipped: Option<String>,
+    },
+    /// Back to the editor, replacing the buffer first if `buffer` is set
+    Edit {
+        buffer: Option<String>,
+    },
 }
 
-pub async fn print_changes(input: &str, preview: bool) -> anyhow::Result<Action> {
-    let noil_index = parse_input(input).context("parse input")?;
-
-    fn print_op(key: &str, index: Option<&str>, path: Option<&Path>) {
-        match index {
-            Some(index) => match path {
-                Some(path) => println!("  - {key} ({index}) - {}", path.display()),
-
-                None => println!("  - {key} ({index})"),
-            },
-            None => match path {
-                Some(path) => {
-                    println!("  - {key} - {}", path.display())
-                }
-                None => println!("  - {key}"),
-            },
+/// Previews the changes of a buffer and asks whether to apply them. With a filesystem the
+/// preview describes every operation in detail, see [`noil_core::preview_with`]. With
+/// `interactive`, or if answered with `i`, every operation is confirmed on its own, see
+/// [`choose_operations`].
+pub async fn print_changes(
+    input: &str,
+    preview: bool,
+    format: OutputFormat,
+    syntax: &Syntax,
+    fs: Option<&dyn FileSystem>,
+    color: bool,
+    interactive: bool,
+) -> anyhow::Result<Action> {
+    let noil_index = noil_core::parse_with(input, syntax).context("parse input")?;
+
+    if format == OutputFormat::Json {
+        let report = Report::planned(&noil_core::plan(&noil_index));
+        println!("{}", report.to_json()?);
+
+        if preview {
+            return Ok(Action::Quit);
         }
     }
 
     eprintln!("Changes:\n");
 
-    for item in noil_index.files {
-        match item.entry.operation {
-            Operation::Add => print_op(
-                &format!("{}", ansi_term::Color::Green.bold().paint("ADD")),
-                None,
-                Some(&item.path),
-            ),
-            Operation::Copy { index } => print_op(
-                &format!("{}", ansi_term::Color::Blue.bold().paint("COPY")),
-                Some(&index),
-                Some(&item.path),
-            ),
-            Operation::Delete { index } => print_op(
-                &format!("{}", ansi_term::Color::Red.bold().paint("DELETE")),
-                Some(&index),
-                Some(&item.path),
-            ),
-            Operation::Move { index } => print_op(
-                &format!(
-                    "{}",
-                    // Orange
-                    ansi_term::Color::RGB(224, 145, 64).bold().paint("MOVE")
-                ),
-                Some(&index),
-                Some(&item.path),
-            ),
-            _ => {}
-        }
+    match fs {
+        Some(fs) => print!("{}", noil_core::preview_with(fs, &noil_index, color).await),
+        None => print!("{}", noil_core::preview(&noil_index, color)),
     }
 
     if preview {
         return Ok(Action::Quit);
     }
 
-    eprint!("\nApply changes? (y (yes) / n (abort) / E (edit)): ");
-    let mut stderr = std::io::stderr();
-    stderr.flush()?;
-
-    let stdin = tokio::io::stdin();
-    let mut reader = BufReader::new(stdin);
-    let mut input_buf = String::new();
+    if interactive {
+        return choose_operations(input, syntax, color).await;
+    }
 
-    reader.read_line(&mut input_buf).await?;
-    let trimmed = input_buf.trim().to_lowercase();
+    eprint!("\nApply changes? (y (yes) / n (abort) / i (interactive) / E (edit)): ");
+    let trimmed = read_answer().await?;
 
     match trimmed.as_str() {
         "y" => {
@@ -86,30 +69,206 @@ pub async fn print_changes(input: &str, preview: bool) -> anyhow::Result<Action>
 
             Ok(Action::Apply {
                 original: input.to_string(),
+                skipped: None,
             })
         }
         "n" => {
             println!("Aborted.");
             Ok(Action::Quit)
         }
+        "i" => choose_operations(input, syntax, color).await,
         "e" | "" => {
             println!("Edit");
 
-            Ok(Action::Edit)
+            Ok(Action::Edit { buffer: None })
         }
         _ => {
             println!("Invalid input: {}", Color::Red.normal().paint(trimmed));
 
             eprint!("press enter to edit: ");
-            let mut stderr = std::io::stderr();
-            stderr.flush()?;
+            read_answer().await?;
+
+            Ok(Action::Edit { buffer: None })
+        }
+    }
+}
+
+/// Walks through the operations of the buffer, asking whether to apply, skip or edit each one,
+/// or quit. Skipped operations are commented out of the buffer which is applied, and applied
+/// ones out of the buffer which is kept, such that the skipped ones can be returned to later.
+async fn choose_operations(input: &str, syntax: &Syntax, color: bool) -> anyhow::Result<Action> {
+    let plan = noil_core::plan(&noil_core::parse_with(input, syntax)?);
+
+    let mut answers = Vec::with_capacity(plan.steps.len());
+    let mut edit = false;
+
+    for (i, step) in plan.steps.iter().enumerate() {
+        let operation = step.operation.to_string();
+        let operation = if color {
+            Color::White.bold().paint(operation).to_string()
+        } else {
+            operation
+        };
+        let description = match &step.source {
+            Some(source) => format!("{} -> {}", source.display(), step.path.display()),
+            None => step.path.display().to_string(),
+        };
+
+        let answer = loop {
+            eprint!(
+                "[{}/{}] {operation} {description}? (y (yes) / s (skip) / e (edit) / q (quit)): ",
+                i + 1,
+                plan.steps.len()
+            );
+            match read_answer().await?.as_str() {
+                answer @ ("y" | "s" | "e" | "q") => break answer.to_string(),
+                answer => eprintln!("Invalid input: {}", Color::Red.normal().paint(answer)),
+            }
+        };
+
+        match answer.as_str() {
+            "y" => answers.push(true),
+            "s" => answers.push(false),
+            "e" => {
+                edit = true;
+                break;
+            }
+            _ => {
+                println!("Aborted.");
+                return Ok(Action::Quit);
+            }
+        }
+    }
+
+    let (buffer, kept) = split_operations(input, syntax, &answers)?;
+
+    if edit {
+        return Ok(Action::Edit {
+            buffer: Some(buffer),
+        });
+    }
+
+    println!("Confirmed.");
+    Ok(Action::Apply {
+        original: buffer,
+        skipped: answers.contains(&false).then_some(kept),
+    })
+}
 
-            let stdin = tokio::io::stdin();
-            let mut reader = BufReader::new(stdin);
-            let mut input_buf = String::new();
-            reader.read_line(&mut input_buf).await?;
+/// Splits the buffer into the one to apply and the one to keep, by whether each operation is
+/// applied. Operations without an answer are left as they are in both.
+fn split_operations(
+    input: &str,
+    syntax: &Syntax,
+    answers: &[bool],
+) -> anyhow::Result<(String, String)> {
+    let mut answers = answers.iter();
+    let mut applied = Vec::new();
+    let mut kept = Vec::new();
 
-            Ok(Action::Edit)
+    for line in input.lines() {
+        let is_operation = noil_core::parse_with(line, syntax)?
+            .files
+            .first()
+            .is_some_and(|f| {
+                !matches!(
+                    f.entry.operation,
+                    Operation::Existing { .. } | Operation::Open { .. }
+                )
+            });
+
+        match is_operation.then(|| answers.next()).flatten() {
+            Some(true) => {
+                applied.push(line.to_string());
+                kept.push(format!("# {line}"));
+            }
+            Some(false) => {
+                applied.push(format!("# {line}"));
+                kept.push(line.to_string());
+            }
+            None => {
+                applied.push(line.to_string());
+                kept.push(line.to_string());
+            }
+        }
+    }
+
+    let newline = if input.ends_with('\n') { "\n" } else { "" };
+    Ok((applied.join("\n") + newline, kept.join("\n") + newline))
+}
+
+/// Reads the answer to a prompt, lower cased. Answers come from stdin if it is a terminal,
+/// otherwise from the terminal itself, as stdin may well be the buffer, such as for
+/// `cat buffer.noil | noil apply`.
+pub async fn read_answer() -> anyhow::Result<String> {
+    std::io::stderr().flush()?;
+
+    if std::io::stdin().is_terminal() {
+        return read_line(tokio::io::stdin()).await;
+    }
+
+    let tty = tokio::fs::File::open("/dev/tty").await.map_err(|_| {
+        anyhow::anyhow!("no terminal to ask for confirmation, use --yes to apply without asking")
+    })?;
+    read_line(tty).await
+}
+
+async fn read_line(mut reader: impl AsyncRead + Unpin) -> anyhow::Result<String> {
+    // Read byte by byte, a buffered reader could swallow the answers to the next questions
+    let mut answer = Vec::new();
+    loop {
+        match reader.read_u8().await {
+            Ok(b'\n') => break,
+            Ok(byte) => answer.push(byte),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && answer.is_empty() => {
+                anyhow::bail!("no answer, the input was closed, use --yes to apply without asking")
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
         }
     }
+
+    Ok(String::from_utf8_lossy(&answer).trim().to_lowercase())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::path::Path;
+
+    use noil_core::{
+        apply::ApplyOptions,
+        apply_with,
+        fs::{FileSystem, MemoryFileSystem},
+        syntax::Syntax,
+    };
+
+    use super::split_operations;
+
+    #[tokio::test]
+    async fn can_resume_skipped_operations() -> anyhow::Result<()> {
+        let fs = MemoryFileSystem::new()
+            .with_file("/a.txt", "a")
+            .with_file("/b.txt", "b");
+        let input = "abc : /a.txt\necd : /b.txt\nMOVE abc : /c.txt\nMOVE ecd : /d.txt\n";
+        let options = || ApplyOptions {
+            quiet: true,
+            ..Default::default()
+        };
+
+        let (applied, kept) = split_operations(input, &Syntax::default(), &[true, false])?;
+        apply_with(&fs, &applied, options()).await?;
+        pretty_assertions::assert_eq!(vec!["/", "/b.txt", "/c.txt"], fs.paths());
+
+        // The applied MOVE would fail with its destination in the way, if it ran again
+        fs.create_file(Path::new("/a.txt")).await?;
+        apply_with(&fs, &kept, options()).await?;
+
+        pretty_assertions::assert_eq!(
+            "abc : /a.txt\necd : /b.txt\n# MOVE abc : /c.txt\nMOVE ecd : /d.txt\n",
+            kept
+        );
+        pretty_assertions::assert_eq!(vec!["/", "/a.txt", "/c.txt", "/d.txt"], fs.paths());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,297 @@
+//! Defaults read from config files, in order of precedence:
+//!
+//! 1. flags on the command line
+//! 2. `.noil.toml` in the current directory, or the closest parent which has one
+//! 3. `~/.config/noil/config.toml`, or `$XDG_CONFIG_HOME/noil/config.toml`, or `$NOIL_CONFIG`
+//! 4. the built in defaults
+//!
+//! A project file can't run commands, as it may come from anywhere, such as a cloned repository.
+//! It can only set `ignore` and `protected` globs, `aliases` and `max_deletes`, and it can only
+//! lower `max_deletes`. Globs are combined with the user config, aliases of the project take
+//! precedence.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use noil_core::{
+    apply::Conflict,
+    fs::{FileSystem, FilteredFileSystem},
+    guard::Guard,
+    hooks::Hooks,
+    syntax::Syntax,
+};
+use serde::Deserialize;
+
+pub const PROJECT_FILE: &str = ".noil.toml";
+
+/// Deleting more files than this requires `--force`, unless the config says otherwise
+pub const MAX_DELETES: usize = 1000;
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The command to edit buffers with, in place of `$VISUAL` and `$EDITOR`
+    pub editor: Option<String>,
+    /// `false` is the same as `--no-color`
+    pub color: Option<bool>,
+    /// Globs to leave out of listings, on top of hidden and `.gitignore`d files
+    pub ignore: Vec<String>,
+    /// Default for `--conflict`
+    pub conflict: Option<Conflict>,
+    /// Default for `--trash`
+    pub trash: Option<bool>,
+    /// Where trashed files go, `~/.local/share/noil/trash` by default
+    pub trash_dir: Option<PathBuf>,
+    /// Default for `--jobs`
+    pub jobs: Option<usize>,
+    /// Default for `--quiet`
+    pub quiet: Option<bool>,
+    /// Default for `--git`
+    pub git: Option<bool>,
+    /// Default for `--git-add`
+    pub git_add: Option<bool>,
+    /// Globs of paths which can't be deleted, moved or written to, see [`noil_core::guard`]
+    pub protected: Vec<String>,
+    /// Deleting more files than this requires `--force`, [`MAX_DELETES`] by default
+    pub max_deletes: Option<usize>,
+    /// Other names for operations, such as `RM = "DELETE"`
+    pub aliases: BTreeMap<String, String>,
+    /// User defined operations and the command they run, such as
+    /// `COMPRESS = "gzip -c {src} > {dst}"`
+    pub operations: BTreeMap<String, String>,
+    /// Commands to run around applying a buffer, see [`noil_core::hooks`]
+    pub hooks: Hooks,
+}
+
+/// The keys a `.noil.toml` may set, see the module docs
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub ignore: Vec<String>,
+    pub protected: Vec<String>,
+    pub aliases: BTreeMap<String, String>,
+    pub max_deletes: Option<usize>,
+}
+
+impl Config {
+    /// Reads the user config and layers the project config on top, missing files are skipped
+    pub async fn load() -> anyhow::Result<Self> {
+        let mut config = match user_config_path() {
+            Some(path) => read(&path).await?.unwrap_or_default(),
+            None => Self::default(),
+        };
+
+        if let Some(path) = project_config_path()
+            && let Some(project) = read(&path).await.context(
+                "a project config can only set ignore, protected, aliases and max_deletes, \
+                 set anything else in the user config",
+            )?
+        {
+            config = config.with_project(project);
+        }
+
+        Ok(config)
+    }
+
+    /// Layers the project config on top of `self`
+    fn with_project(self, project: ProjectConfig) -> Self {
+        Self {
+            ignore: self.ignore.into_iter().chain(project.ignore).collect(),
+            protected: self
+                .protected
+                .into_iter()
+                .chain(project.protected)
+                .collect(),
+            aliases: self.aliases.into_iter().chain(project.aliases).collect(),
+            max_deletes: match (self.max_deletes, project.max_deletes) {
+                (_, None) => self.max_deletes,
+                (max, Some(project)) => Some(project.min(max.unwrap_or(MAX_DELETES))),
+            },
+            ..self
+        }
+    }
+
+    /// The aliases and user defined operations buffers may use
+    pub fn syntax(&self) -> anyhow::Result<Syntax> {
+        Syntax::new(self.aliases.clone(), self.operations.clone()).context("config")
+    }
+
+    /// The guard against destructive plans, `force` skips the delete threshold
+    pub fn guard(&self, force: bool) -> anyhow::Result<Guard> {
+        Guard::new(
+            &self.protected,
+            Some(self.max_deletes.unwrap_or(MAX_DELETES)),
+            force,
+        )
+        .context("config: protected")
+    }
+
+    /// Whether output should be colored, given `--no-color`
+    pub fn color(&self, no_color: bool) -> bool {
+        !no_color && self.color.unwrap_or(true)
+    }
+
+    /// The filesystem to list paths with, leaving out the ignored globs
+    pub fn listing_filesystem(&self) -> anyhow::Result<Box<dyn FileSystem>> {
+        let fs = Box::new(noil_core::fs::RealFileSystem);
+        if self.ignore.is_empty() {
+            return Ok(fs);
+        }
+
+        Ok(Box::new(
+            FilteredFileSystem::new(fs, &self.ignore).context("config: ignore")?,
+        ))
+    }
+
+    /// The trash directory for a single run, such that runs don't trip over each other
+    pub fn trash_run_dir(&self) -> anyhow::Result<PathBuf> {
+        let dir = match &self.trash_dir {
+            Some(dir) => dir.clone(),
+            None => data_dir()
+                .context("no home directory to put the trash in, set trash_dir")?
+                .join("noil")
+                .join("trash"),
+        };
+
+        Ok(dir.join(format!(
+            "{}-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id()
+        )))
+    }
+}
+
+/// Parses a config file, `None` if it doesn't exist
+async fn read<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<Option<T>> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("read config {}", path.display())),
+    };
+
+    tracing::debug!(path = %path.display(), "loading config");
+    toml::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("parse config {}", path.display()))
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NOIL_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+
+    Some(config_dir.join("noil").join("config.toml"))
+}
+
+/// The closest `.noil.toml`, starting from the current directory
+fn project_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+fn data_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(home_dir()?.join(".local").join("share")),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use noil_core::{apply::Conflict, hooks::Hooks};
+
+    use super::{Config, MAX_DELETES, ProjectConfig};
+
+    #[test]
+    fn project_config_takes_precedence() -> anyhow::Result<()> {
+        let user: Config = toml::from_str(
+            r#"
+editor = "vim"
+conflict = "skip"
+ignore = ["*.log"]
+jobs = 4
+
+[aliases]
+RM = "DELETE"
+
+[hooks]
+post_apply = ["notify-send noil"]
+"#,
+        )?;
+        let project: ProjectConfig = toml::from_str(
+            r#"
+ignore = ["target/"]
+max_deletes = 50
+
+[aliases]
+RM = "TRASH"
+"#,
+        )?;
+
+        let config = user.with_project(project);
+
+        pretty_assertions::assert_eq!(
+            Config {
+                editor: Some("vim".into()),
+                conflict: Some(Conflict::Skip),
+                ignore: vec!["*.log".into(), "target/".into()],
+                jobs: Some(4),
+                max_deletes: Some(50),
+                aliases: [("RM".into(), "TRASH".into())].into(),
+                hooks: Hooks {
+                    post_apply: vec!["notify-send noil".into()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            config
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn project_config_cant_run_commands() -> anyhow::Result<()> {
+        for key in [
+            "editor = \"sh\"",
+            "[operations]\nX = \"rm -rf ~\"",
+            "[hooks]\npre_apply = [\"curl evil\"]",
+            "conflict = \"overwrite\"",
+        ] {
+            assert!(toml::from_str::<ProjectConfig>(key).is_err(), "{key}");
+        }
+
+        let project = ProjectConfig {
+            max_deletes: Some(MAX_DELETES * 10),
+            ..Default::default()
+        };
+        pretty_assertions::assert_eq!(
+            Some(MAX_DELETES),
+            Config::default().with_project(project).max_deletes
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_unknown_keys() {
+        assert!(toml::from_str::<Config>("colour = false").is_err());
+    }
+}
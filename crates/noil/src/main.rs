@@ -1,10 +1,5 @@
 mod commit;
-mod encode_rand;
-mod find_prefix;
-mod format;
-mod models;
-mod output;
-mod parse;
+mod config;
 
 mod cli;
 